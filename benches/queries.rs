@@ -0,0 +1,72 @@
+//! Range queries over multi-year synthetic data: `cargo bench`
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use worktime::{Database, SpecialDayEntry, SpecialDayType, WorktimeEntry};
+
+/// Two entries with a lunch break on every weekday and a vacation day per month, until today
+fn synthetic_database(years: i64) -> Database {
+    use chrono::Datelike;
+    let today = chrono::Local::now().date_naive();
+    let mut rows = Vec::new();
+    let mut special_days = Vec::new();
+    let mut day = today - chrono::Duration::days(365 * years);
+    while day < today {
+        if day.weekday().num_days_from_monday() < 5 {
+            let start_of_day = worktime::utils::start_of_day(day);
+            for (start, end) in [(8 * 60, 12 * 60), (12 * 60 + 30, 16 * 60 + 45)] {
+                rows.push(WorktimeEntry {
+                    start: start_of_day + chrono::Duration::minutes(start),
+                    end: start_of_day + chrono::Duration::minutes(end),
+                    comments: String::new(),
+                });
+            }
+        }
+        if day.day() == 15 {
+            special_days.push(SpecialDayEntry {
+                day,
+                day_type: SpecialDayType::Vacation,
+            });
+        }
+        day = day.succ_opt().unwrap();
+    }
+    Database::in_memory(rows, special_days)
+}
+
+fn bench_queries(c: &mut Criterion) {
+    let now = chrono::Local::now();
+    let mut group = c.benchmark_group("queries");
+    for years in [1, 5, 20] {
+        let db = synthetic_database(years);
+        let day = Database::get_day_bounds(now - chrono::Duration::days(100));
+
+        group.bench_with_input(BenchmarkId::new("query_day", years), &db, |b, db| {
+            b.iter(|| db.query(black_box(day)).count())
+        });
+        // what query did before the index:
+        group.bench_with_input(BenchmarkId::new("linear_scan_day", years), &db, |b, db| {
+            b.iter(|| {
+                db.rows()
+                    .iter()
+                    .filter(|x| x.end >= black_box(day).0 && x.start < black_box(day).1)
+                    .count()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("query_special_days_year", years), &db, |b, db| {
+            b.iter(|| db.query_special_days(black_box((now - chrono::Duration::days(365), now))).count())
+        });
+        group.bench_with_input(BenchmarkId::new("get_day_sums_365", years), &db, |b, db| {
+            b.iter(|| db.get_day_sums(black_box(365)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_overtime(c: &mut Criterion) {
+    let now = chrono::Local::now();
+    let db = synthetic_database(5);
+    c.bench_function("calculate_overtime_5_years", |b| {
+        b.iter(|| db.calculate_overtime(chrono::Duration::hours(40), black_box((now - chrono::Duration::days(5 * 365), now))))
+    });
+}
+
+criterion_group!(benches, bench_queries, bench_overtime);
+criterion_main!(benches);
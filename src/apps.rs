@@ -0,0 +1,600 @@
+use serde::{Deserialize, Serialize};
+use crate::config::{Config, WindowRule};
+use crate::database::Database;
+
+/// The focused window
+#[derive(Debug, PartialEq, Clone)]
+pub struct WindowInfo {
+    /// application, e.g. `jetbrains-idea` or `firefox`
+    pub class: String,
+    pub title: String,
+}
+
+/// Time a window was focused. Stored in a side table, so the data file keeps its format.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct AppUsage {
+    pub start: chrono::DateTime<chrono::offset::Local>,
+    pub end: chrono::DateTime<chrono::offset::Local>,
+    pub class: String,
+    pub title: String,
+}
+
+/// Reads the application side table. A missing file is an empty table.
+pub fn read_app_usages(path: &std::path::Path) -> Result<Vec<AppUsage>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut rdr = csv::Reader::from_path(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    rdr.deserialize()
+        .map(|result| result.map_err(|e| format!("{}: {}", path.display(), e)))
+        .collect()
+}
+
+/// Appends to the application side table, so it is not rewritten on every save
+pub fn append_app_usages(path: &std::path::Path, usages: &[AppUsage]) -> Result<(), String> {
+    if usages.is_empty() {
+        return Ok(());
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let is_new = file.metadata().map_err(|e| format!("{}: {}", path.display(), e))?.len() == 0;
+    let mut wtr = csv::WriterBuilder::new().has_headers(is_new).from_writer(file);
+    for usage in usages {
+        wtr.serialize(usage).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    wtr.flush().map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Collects samples of the focused window into `AppUsage` ranges
+#[derive(Default)]
+pub struct AppRecorder {
+    pub usages: Vec<AppUsage>,
+    /// number of usages which are stored in the app file already
+    stored: usize,
+    previous_sample: Option<chrono::DateTime<chrono::offset::Local>>,
+}
+
+impl AppRecorder {
+    /// `usages` as read from the app file
+    pub fn new(usages: Vec<AppUsage>) -> Self {
+        Self {
+            stored: usages.len(),
+            usages,
+            previous_sample: None,
+        }
+    }
+
+    /// Appends the usages which are not stored yet to the app file. The last usage may still be
+    /// extended by the next sample, so it is only stored once it is `finished` or followed by another.
+    pub fn store(self: &mut Self, path: &std::path::Path, finished: bool) -> Result<(), String> {
+        let end = if finished { self.usages.len() } else { self.usages.len().saturating_sub(1) };
+        if end > self.stored {
+            append_app_usages(path, &self.usages[self.stored..end])?;
+            self.stored = end;
+        }
+        Ok(())
+    }
+
+    /// Accounts the time since the previous sample to `window`. The first sample after
+    /// `interrupt` (no session, paused, clock jump) only marks the beginning.
+    pub fn record(self: &mut Self, window: Option<WindowInfo>, now: chrono::DateTime<chrono::offset::Local>) {
+        let Some(previous) = self.previous_sample.replace(now) else {
+            return;
+        };
+        let Some(window) = window else {
+            return;
+        };
+        if now <= previous {
+            return;
+        }
+        if let Some(last) = self.usages.last_mut() {
+            if last.end == previous && last.class == window.class && last.title == window.title {
+                last.end = now;
+                return;
+            }
+        }
+        self.usages.push(AppUsage {
+            start: previous,
+            end: now,
+            class: window.class,
+            title: window.title,
+        });
+    }
+
+    pub fn interrupt(self: &mut Self) {
+        self.previous_sample = None;
+    }
+}
+
+/// The usages overlapping `range`, cut to it
+fn clipped<'a>(
+    usages: &'a [AppUsage],
+    range: (chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>),
+) -> impl Iterator<Item = (&'a AppUsage, chrono::Duration)> {
+    usages
+        .iter()
+        .filter(move |usage| usage.start < range.1 && range.0 < usage.end)
+        .map(move |usage| (usage, std::cmp::min(usage.end, range.1) - std::cmp::max(usage.start, range.0)))
+}
+
+/// Time per application within `range`, longest first
+pub fn breakdown(
+    usages: &[AppUsage],
+    range: (chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>),
+) -> Vec<(String, chrono::Duration)> {
+    let mut sums: Vec<(String, chrono::Duration)> = Vec::new();
+    for (usage, duration) in clipped(usages, range) {
+        match sums.iter_mut().find(|(class, _)| *class == usage.class) {
+            Some((_, sum)) => *sum = *sum + duration,
+            None => sums.push((usage.class.clone(), duration)),
+        }
+    }
+    sums.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sums
+}
+
+/// `WindowRule`s with compiled regular expressions
+pub struct Rules {
+    rules: Vec<(regex::Regex, regex::Regex, String)>,
+}
+
+impl Rules {
+    pub fn compile(rules: &[WindowRule]) -> Result<Self, String> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let class = regex::Regex::new(&rule.class).map_err(|e| format!("window rule class: {}", e))?;
+                let title = regex::Regex::new(&rule.title).map_err(|e| format!("window rule title: {}", e))?;
+                Ok((class, title, rule.comment.clone()))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Comment of the first rule matching the window. `$1` etc. in the comment are replaced
+    /// by the groups captured from the title.
+    pub fn comment_for(self: &Self, class: &str, title: &str) -> Option<String> {
+        self.rules.iter().find_map(|(class_re, title_re, comment)| {
+            if !class_re.is_match(class) {
+                return None;
+            }
+            let captures = title_re.captures(title)?;
+            let mut expanded = String::new();
+            captures.expand(comment, &mut expanded);
+            Some(expanded)
+        })
+    }
+
+    /// The comment whose windows were focused longest within `range`
+    pub fn comment_for_range(
+        self: &Self,
+        usages: &[AppUsage],
+        range: (chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>),
+    ) -> Option<String> {
+        let mut sums: Vec<(String, chrono::Duration)> = Vec::new();
+        for (usage, duration) in clipped(usages, range) {
+            let Some(comment) = self.comment_for(&usage.class, &usage.title) else {
+                continue;
+            };
+            match sums.iter_mut().find(|(c, _)| *c == comment) {
+                Some((_, sum)) => *sum = *sum + duration,
+                None => sums.push((comment, duration)),
+            }
+        }
+        sums.into_iter().max_by_key(|(_, duration)| *duration).map(|(comment, _)| comment)
+    }
+}
+
+/// Window tracking while monitoring: samples the focused window during sessions and comments
+/// the running session by the `window_rules`. Sampling is separate from `update`, so the round trips
+/// to the X server happen without holding the database.
+#[derive(Default)]
+pub struct WindowTracker {
+    sampler: Option<WindowSampler>,
+    connect_failed: bool,
+    rules: Option<(Vec<WindowRule>, Rules)>,
+    /// comment set by the rules, so it may be replaced as long as the user did not change it
+    auto_comment: Option<(chrono::DateTime<chrono::offset::Local>, String)>,
+    /// focused window of the last `sample`, until `update` records it
+    sample: Option<Option<WindowInfo>>,
+}
+
+impl WindowTracker {
+    /// Reads the focused window for the next `update`. Call it before locking the database.
+    pub fn sample(self: &mut Self, cfg: &Config, report: bool) {
+        self.sample = self.focused_window(cfg, report);
+    }
+
+    /// Some(None) if no window is focused, None if windows are not tracked or could not be read
+    fn focused_window(self: &mut Self, cfg: &Config, report: bool) -> Option<Option<WindowInfo>> {
+        if !cfg.track_windows {
+            return None;
+        }
+        if self.sampler.is_none() && !self.connect_failed {
+            match WindowSampler::connect() {
+                Ok(sampler) => self.sampler = Some(sampler),
+                Err(err) => {
+                    if report {
+                        eprintln!("Window tracking disabled: {}", err);
+                    }
+                    self.connect_failed = true;
+                }
+            }
+        }
+        match self.sampler.as_ref()?.focused() {
+            Ok(window) => Some(window),
+            Err(err) => {
+                if report {
+                    eprintln!("Failed to read the focused window: {}", err);
+                }
+                None
+            }
+        }
+    }
+
+    /// Call periodically, after `sample`. `session_start` is the start of the running session,
+    /// None if there is none.
+    pub fn update(
+        self: &mut Self,
+        cfg: &Config,
+        recorder: &mut AppRecorder,
+        db: &mut Database,
+        session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+        now: chrono::DateTime<chrono::offset::Local>,
+        report: bool,
+    ) {
+        let active = session_start.is_some_and(|start| db.effective_session_start(start, now).is_some());
+        let (Some(window), true) = (self.sample.take(), active) else {
+            recorder.interrupt();
+            return;
+        };
+        recorder.record(window, now);
+
+        if self.rules.as_ref().map_or(true, |(source, _)| *source != cfg.window_rules) {
+            match Rules::compile(&cfg.window_rules) {
+                Ok(rules) => self.rules = Some((cfg.window_rules.clone(), rules)),
+                Err(err) => {
+                    if report {
+                        eprintln!("Invalid window rules: {}", err);
+                    }
+                    self.rules = Some((cfg.window_rules.clone(), Rules { rules: Vec::new() }));
+                }
+            }
+        }
+        if let (Some(session_start), Some((_, rules))) = (session_start, &self.rules) {
+            auto_comment(db, rules, &recorder.usages, &mut self.auto_comment, session_start, now);
+        }
+    }
+}
+
+/// Comments the running session by the rules, unless the user commented it. Only the usages
+/// since the start of the session are looked at.
+pub fn auto_comment(
+    db: &mut Database,
+    rules: &Rules,
+    usages: &[AppUsage],
+    previous_auto_comment: &mut Option<(chrono::DateTime<chrono::offset::Local>, String)>,
+    session_start: chrono::DateTime<chrono::offset::Local>,
+    now: chrono::DateTime<chrono::offset::Local>,
+) {
+    let pieces = db.in_progress_pieces(session_start, now);
+    let Some(first) = pieces.first() else {
+        return;
+    };
+    let set_by_rules = previous_auto_comment
+        .as_ref()
+        .is_some_and(|(start, comment)| *start == session_start && *comment == first.comments);
+    if !first.comments.is_empty() && !set_by_rules {
+        return;
+    }
+    let since_start = usages.iter().rposition(|usage| usage.end <= first.start).map_or(0, |i| i + 1);
+    if let Some(comment) = rules.comment_for_range(&usages[since_start..], (first.start, now)) {
+        if comment != first.comments {
+            db.set_session_comment(session_start, comment.clone());
+            *previous_auto_comment = Some((session_start, comment));
+        }
+    }
+}
+
+/// Reads the focused window from an X11 server (`DISPLAY`)
+#[cfg(feature = "x11")]
+pub struct WindowSampler {
+    conn: x11rb::rust_connection::RustConnection,
+    root: u32,
+    net_active_window: u32,
+    net_wm_name: u32,
+    utf8_string: u32,
+}
+
+#[cfg(feature = "x11")]
+impl WindowSampler {
+    pub fn connect() -> Result<Self, String> {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::ConnectionExt;
+        let (conn, screen) = x11rb::connect(None).map_err(|e| format!("X11: {}", e))?;
+        let root = conn.setup().roots[screen].root;
+        let intern = |name: &[u8]| -> Result<u32, String> {
+            Ok(conn
+                .intern_atom(false, name)
+                .map_err(|e| format!("X11: {}", e))?
+                .reply()
+                .map_err(|e| format!("X11: {}", e))?
+                .atom)
+        };
+        let net_active_window = intern(b"_NET_ACTIVE_WINDOW")?;
+        let net_wm_name = intern(b"_NET_WM_NAME")?;
+        let utf8_string = intern(b"UTF8_STRING")?;
+        Ok(Self {
+            conn,
+            root,
+            net_active_window,
+            net_wm_name,
+            utf8_string,
+        })
+    }
+
+    fn property(self: &Self, window: u32, property: u32, type_: u32) -> Result<Vec<u8>, String> {
+        use x11rb::protocol::xproto::ConnectionExt;
+        Ok(self
+            .conn
+            .get_property(false, window, property, type_, 0, 4096)
+            .map_err(|e| format!("X11: {}", e))?
+            .reply()
+            .map_err(|e| format!("X11: {}", e))?
+            .value)
+    }
+
+    /// None if no window is focused
+    pub fn focused(self: &Self) -> Result<Option<WindowInfo>, String> {
+        use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+        let reply = self
+            .conn
+            .get_property(false, self.root, self.net_active_window, AtomEnum::WINDOW, 0, 1)
+            .map_err(|e| format!("X11: {}", e))?
+            .reply()
+            .map_err(|e| format!("X11: {}", e))?;
+        let Some(window) = reply.value32().and_then(|mut values| values.next()).filter(|w| *w != 0) else {
+            return Ok(None);
+        };
+        // WM_CLASS is "instance\0class\0"
+        let wm_class = self.property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into())?;
+        let class = wm_class
+            .split(|b| *b == 0)
+            .filter(|part| !part.is_empty())
+            .last()
+            .map(|part| String::from_utf8_lossy(part).to_string())
+            .unwrap_or_default();
+        let mut title = self.property(window, self.net_wm_name, self.utf8_string)?;
+        if title.is_empty() {
+            title = self.property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into())?;
+        }
+        Ok(Some(WindowInfo {
+            class,
+            title: String::from_utf8_lossy(&title).to_string(),
+        }))
+    }
+}
+
+#[cfg(not(feature = "x11"))]
+pub struct WindowSampler;
+
+#[cfg(not(feature = "x11"))]
+impl WindowSampler {
+    pub fn connect() -> Result<Self, String> {
+        Err("window tracking needs a build with the x11 feature".to_string())
+    }
+
+    pub fn focused(self: &Self) -> Result<Option<WindowInfo>, String> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> chrono::DateTime<chrono::offset::Local> {
+        format!("2023-05-03T{}+02:00", time).parse().unwrap()
+    }
+
+    fn window(class: &str, title: &str) -> Option<WindowInfo> {
+        Some(WindowInfo {
+            class: class.into(),
+            title: title.into(),
+        })
+    }
+
+    #[test]
+    fn test_app_recorder_breakdown() {
+        let mut recorder = AppRecorder::default();
+        recorder.record(window("jetbrains-idea", "foo – main.rs"), at("09:00:00"));
+        recorder.record(window("jetbrains-idea", "foo – main.rs"), at("09:00:02"));
+        recorder.record(window("jetbrains-idea", "foo – main.rs"), at("09:00:04"));
+        recorder.record(window("firefox", "docs"), at("09:00:05"));
+        recorder.interrupt();
+        // the time between interrupt and the next sample is not accounted
+        recorder.record(window("firefox", "docs"), at("09:30:00"));
+        recorder.record(None, at("09:30:02"));
+        recorder.record(window("firefox", "docs"), at("09:30:04"));
+        assert_eq!(recorder.usages.len(), 3);
+        assert_eq!(recorder.usages[0].end, at("09:00:04"));
+
+        assert_eq!(
+            breakdown(&recorder.usages, (at("09:00:00"), at("10:00:00"))),
+            vec![
+                ("jetbrains-idea".to_string(), chrono::Duration::seconds(4)),
+                ("firefox".to_string(), chrono::Duration::seconds(3)),
+            ]
+        );
+        assert_eq!(
+            breakdown(&recorder.usages, (at("09:00:03"), at("09:30:00"))),
+            vec![
+                ("firefox".to_string(), chrono::Duration::seconds(1)),
+                ("jetbrains-idea".to_string(), chrono::Duration::seconds(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_app_recorder_store_appends() {
+        let dir = std::env::temp_dir().join(format!("worktime-apps-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("apps.csv");
+        let mut recorder = AppRecorder::new(Vec::new());
+        recorder.record(window("firefox", "docs"), at("09:00:00"));
+        recorder.record(window("firefox", "docs"), at("09:00:02"));
+        // the running usage is not stored yet
+        recorder.store(&path, false).unwrap();
+        assert!(!path.exists());
+
+        recorder.record(window("xterm", "bash"), at("09:00:04"));
+        recorder.store(&path, false).unwrap();
+        assert_eq!(read_app_usages(&path).unwrap(), recorder.usages[..1]);
+        recorder.record(window("xterm", "bash"), at("09:00:06"));
+        recorder.store(&path, true).unwrap();
+        assert_eq!(read_app_usages(&path).unwrap(), recorder.usages);
+
+        // the next monitoring process continues the file
+        let mut recorder = AppRecorder::new(read_app_usages(&path).unwrap());
+        recorder.record(window("firefox", "docs"), at("10:00:00"));
+        recorder.record(window("firefox", "docs"), at("10:00:02"));
+        recorder.store(&path, true).unwrap();
+        assert_eq!(read_app_usages(&path).unwrap(), recorder.usages);
+        assert_eq!(recorder.usages.len(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rules_comment() {
+        let rules = Rules::compile(&[
+            WindowRule {
+                class: "^jetbrains-".into(),
+                title: r"^(\w+) – ".into(),
+                comment: "project $1".into(),
+            },
+            WindowRule {
+                class: "firefox".into(),
+                title: "".into(),
+                comment: "research".into(),
+            },
+        ])
+        .unwrap();
+        assert_eq!(rules.comment_for("jetbrains-idea", "foo – main.rs"), Some("project foo".into()));
+        assert_eq!(rules.comment_for("firefox", "docs"), Some("research".into()));
+        assert_eq!(rules.comment_for("xterm", "bash"), None);
+
+        let usages = vec![
+            AppUsage {
+                start: at("09:00:00"),
+                end: at("09:20:00"),
+                class: "jetbrains-idea".into(),
+                title: "foo – main.rs".into(),
+            },
+            AppUsage {
+                start: at("09:20:00"),
+                end: at("09:30:00"),
+                class: "firefox".into(),
+                title: "docs".into(),
+            },
+            AppUsage {
+                start: at("09:30:00"),
+                end: at("09:40:00"),
+                class: "jetbrains-idea".into(),
+                title: "bar – lib.rs".into(),
+            },
+        ];
+        assert_eq!(
+            rules.comment_for_range(&usages, (at("09:00:00"), at("09:40:00"))),
+            Some("project foo".into())
+        );
+        assert_eq!(
+            rules.comment_for_range(&usages, (at("09:15:00"), at("09:35:00"))),
+            Some("research".into())
+        );
+        assert!(Rules::compile(&[WindowRule {
+            class: "(".into(),
+            title: "".into(),
+            comment: "".into(),
+        }])
+        .is_err());
+    }
+
+    #[test]
+    fn test_auto_comment_keeps_user_comment() {
+        let rules = Rules::compile(&[WindowRule {
+            class: "^jetbrains-".into(),
+            title: r"^(\w+) – ".into(),
+            comment: "project $1".into(),
+        }])
+        .unwrap();
+        let mut usages = vec![AppUsage {
+            start: at("09:00:00"),
+            end: at("09:10:00"),
+            class: "jetbrains-idea".into(),
+            title: "foo – main.rs".into(),
+        }];
+        let mut db = Database::in_memory(vec![], vec![]);
+        let mut previous = None;
+        let session_start = at("09:00:00");
+        auto_comment(&mut db, &rules, &usages, &mut previous, session_start, at("09:10:00"));
+        assert_eq!(db.in_progress_pieces(session_start, at("09:10:00"))[0].comments, "project foo");
+
+        // another project took over
+        usages.push(AppUsage {
+            start: at("09:10:00"),
+            end: at("09:30:00"),
+            class: "jetbrains-idea".into(),
+            title: "bar – lib.rs".into(),
+        });
+        auto_comment(&mut db, &rules, &usages, &mut previous, session_start, at("09:30:00"));
+        assert_eq!(db.in_progress_pieces(session_start, at("09:30:00"))[0].comments, "project bar");
+
+        // a comment of the user is kept
+        db.set_session_comment(session_start, "meeting".into());
+        auto_comment(&mut db, &rules, &usages, &mut previous, session_start, at("09:30:00"));
+        assert_eq!(db.in_progress_pieces(session_start, at("09:30:00"))[0].comments, "meeting");
+    }
+
+    /// Needs an X server without window manager, e.g. `xvfb-run cargo test --features x11`
+    #[cfg(feature = "x11")]
+    #[test]
+    fn test_window_sampler_x11() {
+        use x11rb::connection::Connection;
+        use x11rb::protocol::xproto::{AtomEnum, ConnectionExt, CreateWindowAux, PropMode, WindowClass};
+        use x11rb::wrapper::ConnectionExt as _;
+        if std::env::var_os("DISPLAY").is_none() {
+            eprintln!("DISPLAY not set, skipping");
+            return;
+        }
+        let sampler = WindowSampler::connect().unwrap();
+        let conn = &sampler.conn;
+        let id = conn.generate_id().unwrap();
+        conn.create_window(
+            0,
+            id,
+            sampler.root,
+            0,
+            0,
+            10,
+            10,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            0,
+            &CreateWindowAux::new(),
+        )
+        .unwrap();
+        conn.change_property8(PropMode::REPLACE, id, AtomEnum::WM_CLASS, AtomEnum::STRING, b"idea\0jetbrains-idea\0")
+            .unwrap();
+        conn.change_property8(PropMode::REPLACE, id, sampler.net_wm_name, sampler.utf8_string, "foo – main.rs".as_bytes())
+            .unwrap();
+        // without window manager, set the focus property ourselves
+        conn.change_property32(PropMode::REPLACE, sampler.root, sampler.net_active_window, AtomEnum::WINDOW, &[id])
+            .unwrap();
+        conn.flush().unwrap();
+
+        assert_eq!(sampler.focused().unwrap(), window("jetbrains-idea", "foo – main.rs"));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::duration::Rounding;
+use crate::models::WorktimeEntry;
+
+/// What billing rounding is applied to
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BillingScope {
+    /// every entry of a day on its own
+    Entry,
+    /// the sum of a day
+    Day,
+}
+
+/// Rounding of billed time, e.g. every entry up to the next 15 minutes. Only applied when
+/// reporting and exporting, stored entries are never changed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BillingRounding {
+    pub scope: BillingScope,
+    pub increment: chrono::Duration,
+    pub rounding: Rounding,
+}
+
+impl BillingRounding {
+    /// `duration` rounded to a multiple of the increment
+    pub fn round(self: &Self, duration: chrono::Duration) -> chrono::Duration {
+        let increment = self.increment.num_milliseconds();
+        let units = self.rounding.apply(duration.num_milliseconds().abs(), increment);
+        let rounded = chrono::Duration::milliseconds(units * increment);
+        if duration < chrono::Duration::zero() { -rounded } else { rounded }
+    }
+
+    /// Billed time of the entries of one day
+    pub fn billed(self: &Self, entries: &[WorktimeEntry]) -> chrono::Duration {
+        match self.scope {
+            BillingScope::Entry => entries
+                .iter()
+                .fold(chrono::Duration::zero(), |sum, entry| sum + self.round(entry.duration())),
+            BillingScope::Day => {
+                self.round(entries.iter().fold(chrono::Duration::zero(), |sum, entry| sum + entry.duration()))
+            }
+        }
+    }
+
+    /// The entries as billed, e.g. for exports: entries are cut at midnight and their ends moved
+    /// by the rounding. For `Day`, the difference of a day is added to or taken from its last
+    /// entries. Entries rounded to nothing are left out. An entry which would start before the
+    /// billed end of the previous one is moved behind it, so billed entries never overlap.
+    pub fn round_entries(self: &Self, entries: impl Iterator<Item = WorktimeEntry>) -> Vec<WorktimeEntry> {
+        let pieces: Vec<WorktimeEntry> = entries
+            .flat_map(|entry| entry.split_at_day_boundary(chrono::NaiveTime::MIN))
+            .collect();
+        let mut result = Vec::new();
+        match self.scope {
+            BillingScope::Entry => {
+                for mut entry in pieces {
+                    entry.end = entry.start + self.round(entry.duration());
+                    result.push(entry);
+                }
+            }
+            BillingScope::Day => {
+                for day in pieces.chunk_by(|a, b| a.start.date_naive() == b.start.date_naive()) {
+                    let mut day = day.to_vec();
+                    let worked = day.iter().fold(chrono::Duration::zero(), |sum, entry| sum + entry.duration());
+                    let mut difference = self.round(worked) - worked;
+                    if difference > chrono::Duration::zero() {
+                        let last = day.last_mut().unwrap();
+                        last.end = last.end + difference;
+                    }
+                    for entry in day.iter_mut().rev() {
+                        if difference >= chrono::Duration::zero() {
+                            break;
+                        }
+                        let cut = std::cmp::min(-difference, entry.duration());
+                        entry.end = entry.end - cut;
+                        difference = difference + cut;
+                    }
+                    result.extend(day);
+                }
+            }
+        }
+        result.retain(|entry| entry.end > entry.start);
+        let mut previous_end: Option<chrono::DateTime<chrono::offset::Local>> = None;
+        for entry in result.iter_mut() {
+            if let Some(previous_end) = previous_end.filter(|end| *end > entry.start) {
+                let duration = entry.duration();
+                entry.start = previous_end;
+                entry.end = previous_end + duration;
+            }
+            previous_end = Some(entry.end);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start: &str, end: &str) -> WorktimeEntry {
+        WorktimeEntry {
+            start: format!("2023-05-03T{}:00+02:00", start).parse().unwrap(),
+            end: format!("2023-05-03T{}:00+02:00", end).parse().unwrap(),
+            comments: "".into(),
+        }
+    }
+
+    fn billing(scope: BillingScope, minutes: i64, rounding: Rounding) -> BillingRounding {
+        BillingRounding {
+            scope,
+            increment: chrono::Duration::minutes(minutes),
+            rounding,
+        }
+    }
+
+    #[test]
+    fn test_billed_per_entry_and_per_day() {
+        // 50 and 20 minutes
+        let entries = vec![entry("08:00", "08:50"), entry("09:00", "09:20")];
+        let minutes = |billing: BillingRounding| billing.billed(&entries).num_minutes();
+        assert_eq!(minutes(billing(BillingScope::Entry, 15, Rounding::Up)), 60 + 30);
+        assert_eq!(minutes(billing(BillingScope::Day, 15, Rounding::Up)), 75);
+        assert_eq!(minutes(billing(BillingScope::Entry, 30, Rounding::Down)), 30);
+        assert_eq!(minutes(billing(BillingScope::Day, 30, Rounding::Down)), 60);
+        assert_eq!(minutes(billing(BillingScope::Entry, 6, Rounding::Nearest)), 48 + 18);
+        assert_eq!(minutes(billing(BillingScope::Day, 6, Rounding::Nearest)), 72);
+        assert_eq!(billing(BillingScope::Day, 15, Rounding::Up).billed(&[]), chrono::Duration::zero());
+    }
+
+    #[test]
+    fn test_round_entries() {
+        let entries = vec![entry("08:00", "08:50"), entry("09:00", "09:20")];
+        assert_eq!(
+            billing(BillingScope::Entry, 15, Rounding::Up).round_entries(entries.clone().into_iter()),
+            vec![entry("08:00", "09:00"), entry("09:00", "09:30")]
+        );
+        assert_eq!(
+            billing(BillingScope::Entry, 30, Rounding::Down).round_entries(entries.clone().into_iter()),
+            vec![entry("08:00", "08:30")]
+        );
+        assert_eq!(
+            billing(BillingScope::Day, 15, Rounding::Up).round_entries(entries.clone().into_iter()),
+            vec![entry("08:00", "08:50"), entry("09:00", "09:25")]
+        );
+        assert_eq!(
+            billing(BillingScope::Day, 60, Rounding::Down).round_entries(entries.clone().into_iter()),
+            vec![entry("08:00", "08:50"), entry("09:00", "09:10")]
+        );
+        assert_eq!(
+            billing(BillingScope::Day, 45, Rounding::Down).round_entries(entries.clone().into_iter()),
+            vec![entry("08:00", "08:45")]
+        );
+
+        // closely spaced entries are moved behind the rounded up previous ones
+        let entries = vec![entry("08:00", "08:50"), entry("08:55", "09:20"), entry("09:25", "10:00")];
+        assert_eq!(
+            billing(BillingScope::Entry, 15, Rounding::Up).round_entries(entries.clone().into_iter()),
+            vec![entry("08:00", "09:00"), entry("09:00", "09:30"), entry("09:30", "10:15")]
+        );
+        // the rounded up day reaches into the next one
+        let next_day = |start: &str, end: &str| WorktimeEntry {
+            start: format!("2023-05-04T{}:00+02:00", start).parse().unwrap(),
+            end: format!("2023-05-04T{}:00+02:00", end).parse().unwrap(),
+            comments: "".into(),
+        };
+        assert_eq!(
+            billing(BillingScope::Day, 60, Rounding::Up)
+                .round_entries(vec![entry("23:05", "23:50"), next_day("00:00", "00:30")].into_iter()),
+            vec![
+                WorktimeEntry {
+                    start: entry("23:05", "23:50").start,
+                    end: next_day("00:05", "00:05").end,
+                    comments: "".into(),
+                },
+                next_day("00:05", "01:05"),
+            ]
+        );
+    }
+}
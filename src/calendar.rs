@@ -0,0 +1,642 @@
+use chrono::{offset::TimeZone, Datelike, Local};
+use crate::database::Database;
+use crate::models::WorktimeEntry;
+use crate::utils::{local_time_on, start_of_day};
+
+/// A (non all-day) event read from an iCalendar file
+#[derive(Debug, PartialEq, Clone)]
+pub struct CalendarEvent {
+    pub start: chrono::DateTime<chrono::offset::Local>,
+    pub end: chrono::DateTime<chrono::offset::Local>,
+    pub summary: String,
+}
+
+fn unescape_ics_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => (),
+        }
+    }
+    unescaped
+}
+
+/// Parses DATE-TIME values. UTC values ("...Z") and values in the time zone `tz` (of a TZID
+/// parameter) are converted, floating values are interpreted as local time.
+/// Returns None for all-day DATE values.
+fn parse_ics_datetime(
+    value: &str,
+    tz: Option<chrono_tz::Tz>,
+) -> Result<Option<chrono::DateTime<chrono::offset::Local>>, String> {
+    if value.len() == 8 {
+        return Ok(None);
+    }
+    if let Some(utc_value) = value.strip_suffix('Z') {
+        let naive = chrono::NaiveDateTime::parse_from_str(utc_value, "%Y%m%dT%H%M%S")
+            .map_err(|e| format!("invalid date-time '{}': {}", value, e))?;
+        return Ok(Some(chrono::Utc.from_utc_datetime(&naive).with_timezone(&Local)));
+    }
+    let naive = chrono::NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .map_err(|e| format!("invalid date-time '{}': {}", value, e))?;
+    let time = match tz {
+        Some(tz) => tz.from_local_datetime(&naive).earliest().map(|time| time.with_timezone(&Local)),
+        None => Local.from_local_datetime(&naive).earliest(),
+    };
+    match (time, tz) {
+        (Some(time), _) => Ok(Some(time)),
+        (None, Some(tz)) => Err(format!("date-time '{}' does not exist in time zone {}", value, tz)),
+        (None, None) => Err(format!("date-time '{}' does not exist in local time zone", value)),
+    }
+}
+
+/// The time zone of a TZID parameter, if it is a name of the IANA time zone database
+fn parse_tzid(tzid: &str) -> Option<chrono_tz::Tz> {
+    tzid.trim_matches('"').parse().ok()
+}
+
+/// A DATE-TIME or DATE value of EXDATE or RECURRENCE-ID. A DATE matches the occurrence on that day.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum OccurrenceId {
+    Time(chrono::DateTime<chrono::offset::Local>),
+    Day(chrono::NaiveDate),
+}
+
+impl OccurrenceId {
+    fn parse(value: &str, tz: Option<chrono_tz::Tz>) -> Result<Self, String> {
+        match parse_ics_datetime(value, tz)? {
+            Some(time) => Ok(OccurrenceId::Time(time)),
+            None => chrono::NaiveDate::parse_from_str(value, "%Y%m%d")
+                .map(OccurrenceId::Day)
+                .map_err(|e| format!("invalid date '{}': {}", value, e)),
+        }
+    }
+
+    fn matches(self: &Self, start: chrono::DateTime<chrono::offset::Local>) -> bool {
+        match self {
+            OccurrenceId::Time(time) => *time == start,
+            OccurrenceId::Day(day) => *day == start.date_naive(),
+        }
+    }
+}
+
+/// A VEVENT as written in the file, before its recurrences are expanded
+#[derive(Default)]
+struct RawEvent {
+    uid: String,
+    start: Option<chrono::DateTime<chrono::offset::Local>>,
+    end: Option<chrono::DateTime<chrono::offset::Local>>,
+    summary: String,
+    all_day: bool,
+    cancelled: bool,
+    rrule: Option<String>,
+    excluded: Vec<OccurrenceId>,
+    /// set for a moved or cancelled occurrence of a recurring event with the same UID
+    recurrence_id: Option<OccurrenceId>,
+}
+
+/// The supported part of an RRULE: daily or weekly, with INTERVAL, COUNT, UNTIL and BYDAY
+#[derive(Debug, PartialEq)]
+struct Recurrence {
+    weekly: bool,
+    interval: i64,
+    count: Option<usize>,
+    /// the last possible start
+    until: Option<chrono::DateTime<chrono::offset::Local>>,
+    weekdays: Vec<chrono::Weekday>,
+}
+
+fn parse_rrule(value: &str) -> Result<Recurrence, String> {
+    let mut recurrence = Recurrence {
+        weekly: false,
+        interval: 1,
+        count: None,
+        until: None,
+        weekdays: Vec::new(),
+    };
+    for part in value.split(';') {
+        let (key, value) = part.split_once('=').unwrap_or((part, ""));
+        match key.to_ascii_uppercase().as_str() {
+            "FREQ" => match value.to_ascii_uppercase().as_str() {
+                "DAILY" => recurrence.weekly = false,
+                "WEEKLY" => recurrence.weekly = true,
+                _ => return Err(format!("FREQ={} is not supported", value)),
+            },
+            "INTERVAL" => {
+                recurrence.interval = value
+                    .parse()
+                    .ok()
+                    .filter(|interval| *interval > 0)
+                    .ok_or_else(|| format!("invalid INTERVAL '{}'", value))?;
+            }
+            "COUNT" => recurrence.count = Some(value.parse().map_err(|_| format!("invalid COUNT '{}'", value))?),
+            "UNTIL" => {
+                recurrence.until = Some(match OccurrenceId::parse(value, None)? {
+                    OccurrenceId::Time(time) => time,
+                    OccurrenceId::Day(day) => start_of_day(day.succ_opt().unwrap()) - chrono::Duration::nanoseconds(1),
+                });
+            }
+            "BYDAY" => {
+                for day in value.split(',') {
+                    let weekday = match day.to_ascii_uppercase().as_str() {
+                        "MO" => chrono::Weekday::Mon,
+                        "TU" => chrono::Weekday::Tue,
+                        "WE" => chrono::Weekday::Wed,
+                        "TH" => chrono::Weekday::Thu,
+                        "FR" => chrono::Weekday::Fri,
+                        "SA" => chrono::Weekday::Sat,
+                        "SU" => chrono::Weekday::Sun,
+                        _ => return Err(format!("BYDAY={} is not supported", value)),
+                    };
+                    recurrence.weekdays.push(weekday);
+                }
+            }
+            // only matters for weekly rules with an interval, weeks start on monday here
+            "WKST" => (),
+            _ => return Err(format!("{} is not supported", key)),
+        }
+    }
+    Ok(recurrence)
+}
+
+/// Starts of the occurrences of a recurring event, which start before `until`.
+/// Occurrences keep the local time of day of the first one.
+fn occurrences(
+    first: chrono::DateTime<chrono::offset::Local>,
+    recurrence: &Recurrence,
+    until: chrono::DateTime<chrono::offset::Local>,
+) -> Vec<chrono::DateTime<chrono::offset::Local>> {
+    let first_day = first.date_naive();
+    let first_week = first_day.week(chrono::Weekday::Mon).first_day();
+    let mut result = Vec::new();
+    let mut day = first_day;
+    loop {
+        let start = local_time_on(day, first.time());
+        if start >= until
+            || recurrence.until.is_some_and(|last| start > last)
+            || recurrence.count.is_some_and(|count| result.len() >= count)
+        {
+            break;
+        }
+        let in_interval = if recurrence.weekly {
+            (day.week(chrono::Weekday::Mon).first_day() - first_week).num_days() / 7 % recurrence.interval == 0
+        } else {
+            (day - first_day).num_days() % recurrence.interval == 0
+        };
+        let on_weekday = if recurrence.weekdays.is_empty() {
+            !recurrence.weekly || day.weekday() == first_day.weekday()
+        } else {
+            recurrence.weekdays.contains(&day.weekday())
+        };
+        if in_interval && on_weekday {
+            result.push(start);
+        }
+        day = day.succ_opt().unwrap();
+    }
+    result
+}
+
+/// Reads all timed VEVENTs from iCalendar content.
+///
+/// All-day events, cancelled events and events without an end are skipped.
+/// Daily and weekly recurring events are expanded into their occurrences starting before `until`,
+/// without the ones excluded by EXDATE or replaced by an event with a RECURRENCE-ID.
+/// Of events with other recurrence rules only the first occurrence is returned, with a warning.
+/// Times with a TZID of the IANA time zone database (e.g. `Europe/Berlin`) are converted to local
+/// time. Other TZIDs (e.g. Windows names) are read as local time, with a warning.
+pub fn parse_ics(content: &str, until: chrono::DateTime<chrono::offset::Local>) -> Result<Vec<CalendarEvent>, String> {
+    // unfold continuation lines first (RFC 5545 section 3.1):
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        lines.push(line.to_string());
+    }
+
+    let mut raw_events = Vec::new();
+    let mut event: Option<RawEvent> = None;
+    let mut unknown_tzids: Vec<String> = Vec::new();
+    for (line_number, line) in lines.iter().enumerate() {
+        let Some((name_and_params, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut params = name_and_params.split(';');
+        let name = params.next().unwrap_or("").to_ascii_uppercase();
+        let params: Vec<&str> = params.collect();
+        let is_date_value = params.iter().any(|p| p.eq_ignore_ascii_case("VALUE=DATE"));
+        let tzid = params
+            .iter()
+            .find_map(|p| p.split_once('=').filter(|(key, _)| key.eq_ignore_ascii_case("TZID")))
+            .map(|(_, tzid)| tzid);
+        let tz = tzid.and_then(parse_tzid);
+        if let (Some(tzid), None) = (tzid, tz) {
+            if !unknown_tzids.iter().any(|unknown| unknown == tzid) {
+                unknown_tzids.push(tzid.to_string());
+            }
+        }
+        let with_line = |e: String| format!("line {}: {}", line_number + 1, e);
+        if name == "BEGIN" && value.eq_ignore_ascii_case("VEVENT") {
+            event = Some(RawEvent::default());
+            continue;
+        }
+        let Some(current) = event.as_mut() else {
+            continue;
+        };
+        match name.as_str() {
+            "END" if value.eq_ignore_ascii_case("VEVENT") => raw_events.extend(event.take()),
+            "DTSTART" | "DTEND" => {
+                let parsed = if is_date_value { None } else { parse_ics_datetime(value, tz).map_err(with_line)? };
+                if parsed.is_none() {
+                    current.all_day = true;
+                }
+                if name == "DTSTART" {
+                    current.start = parsed;
+                } else {
+                    current.end = parsed;
+                }
+            }
+            "UID" => current.uid = value.to_string(),
+            "SUMMARY" => current.summary = unescape_ics_text(value),
+            "STATUS" => current.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+            "RRULE" => current.rrule = Some(value.to_string()),
+            "EXDATE" => {
+                for excluded in value.split(',') {
+                    current.excluded.push(OccurrenceId::parse(excluded, tz).map_err(with_line)?);
+                }
+            }
+            "RECURRENCE-ID" => current.recurrence_id = Some(OccurrenceId::parse(value, tz).map_err(with_line)?),
+            _ => (),
+        }
+    }
+    for tzid in unknown_tzids {
+        eprintln!("Unknown time zone \"{}\", its times are read as local time", tzid);
+    }
+
+    let replaced: Vec<(&str, OccurrenceId)> = raw_events
+        .iter()
+        .filter_map(|event| event.recurrence_id.map(|id| (event.uid.as_str(), id)))
+        .collect();
+    let mut events = Vec::new();
+    for event in raw_events.iter() {
+        let (Some(start), Some(end), false, false) = (event.start, event.end, event.all_day, event.cancelled) else {
+            continue;
+        };
+        if start >= end {
+            continue;
+        }
+        let starts = match (&event.rrule, event.recurrence_id) {
+            (Some(rrule), None) => match parse_rrule(rrule) {
+                Ok(recurrence) => occurrences(start, &recurrence, until)
+                    .into_iter()
+                    .filter(|occurrence| !event.excluded.iter().any(|excluded| excluded.matches(*occurrence)))
+                    .filter(|occurrence| {
+                        !replaced
+                            .iter()
+                            .any(|(uid, id)| *uid == event.uid && id.matches(*occurrence))
+                    })
+                    .collect(),
+                Err(err) => {
+                    eprintln!(
+                        "Only the first occurrence of the recurring event \"{}\" is used: {}",
+                        event.summary, err
+                    );
+                    vec![start]
+                }
+            },
+            _ => vec![start],
+        };
+        for occurrence in starts {
+            events.push(CalendarEvent {
+                start: occurrence,
+                end: occurrence + (end - start),
+                summary: event.summary.clone(),
+            });
+        }
+    }
+    Ok(events)
+}
+
+/// Returns the parts of `event` which are not yet covered by any worktime entry
+/// in the database or by `in_progress` (the pieces of the running session, which is not
+/// committed yet), as worktime entries commented with the event summary.
+pub fn uncovered_parts(db: &Database, in_progress: &[WorktimeEntry], event: &CalendarEvent) -> Vec<WorktimeEntry> {
+    let mut covered: Vec<WorktimeEntry> = db.query((event.start, event.end)).collect();
+    covered.extend(in_progress.iter().filter(|piece| piece.start < event.end && piece.end > event.start).cloned());
+    covered.sort();
+    let mut result = Vec::new();
+    let mut gap_start = event.start;
+    for entry in covered {
+        if entry.start > gap_start {
+            result.push(WorktimeEntry {
+                start: gap_start,
+                end: entry.start,
+                comments: event.summary.clone(),
+            });
+        }
+        if entry.end > gap_start {
+            gap_start = entry.end;
+        }
+    }
+    if gap_start < event.end {
+        result.push(WorktimeEntry {
+            start: gap_start,
+            end: event.end,
+            comments: event.summary.clone(),
+        });
+    }
+    result
+}
+
+/// Fills all breaks inside `range` which are overlapped by a calendar event.
+/// Events are cut to `range`, time of the running session (`in_progress`) is not a break.
+/// Returns the entries which were added.
+pub fn fill_gaps(
+    db: &mut Database,
+    in_progress: &[WorktimeEntry],
+    events: &[CalendarEvent],
+    range: (
+        chrono::DateTime<chrono::offset::Local>,
+        chrono::DateTime<chrono::offset::Local>,
+    ),
+) -> Vec<WorktimeEntry> {
+    let mut added = Vec::new();
+    for event in events {
+        if event.end <= range.0 || event.start >= range.1 {
+            continue;
+        }
+        let cut_event = CalendarEvent {
+            start: std::cmp::max(event.start, range.0),
+            end: std::cmp::min(event.end, range.1),
+            summary: event.summary.clone(),
+        };
+        for entry in uncovered_parts(db, in_progress, &cut_event) {
+            db.insert_worktime(entry.clone());
+            added.push(entry);
+        }
+    }
+    added
+}
+
+/// Events of an iCalendar file, recurring events expanded until `until` (see `parse_ics`)
+pub fn read_ics_file(
+    path: &std::path::Path,
+    until: chrono::DateTime<chrono::offset::Local>,
+) -> Result<Vec<CalendarEvent>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("read {}: {}", path.display(), e))?;
+    parse_ics(&content, until).map_err(|e| format!("parse {}: {}", path.display(), e))
+}
+
+/// Automatic mode: fills breaks inside `range` from the configured calendar file.
+/// The file is re-read on every call, so changes to it are picked up.
+pub fn auto_fill_gaps(
+    db: &mut Database,
+    in_progress: &[WorktimeEntry],
+    calendar_file: &str,
+    range: (
+        chrono::DateTime<chrono::offset::Local>,
+        chrono::DateTime<chrono::offset::Local>,
+    ),
+) {
+    let path = expanduser::expanduser(calendar_file).unwrap();
+    match read_ics_file(&path, range.1) {
+        Ok(events) => {
+            for entry in fill_gaps(db, in_progress, &events, range) {
+                println!(
+                    "Added calendar event {} -> {} \"{}\"",
+                    entry.start.format("%F %T"),
+                    entry.end.format("%T"),
+                    entry.comments
+                );
+            }
+        }
+        Err(err) => eprintln!("Could not fill breaks from calendar: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(time: &str) -> chrono::DateTime<chrono::offset::Local> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let content = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART:20230123T090000Z\r\n\
+            DTEND:20230123T100000Z\r\n\
+            SUMMARY:Team meeting\\, weekly\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;VALUE=DATE:20230124\r\n\
+            DTEND;VALUE=DATE:20230125\r\n\
+            SUMMARY:Conference\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART:20230125T090000Z\r\n\
+            DTEND:20230125T100000Z\r\n\
+            STATUS:CANCELLED\r\n\
+            SUMMARY:Cancelled\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART:20230126T090000Z\r\n\
+            DTEND:20230126T093000Z\r\n\
+            SUMMARY:Folded\r\n  summary\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let events = parse_ics(content, local("2024-01-01T00:00:00+00:00")).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                CalendarEvent {
+                    start: local("2023-01-23T09:00:00+00:00"),
+                    end: local("2023-01-23T10:00:00+00:00"),
+                    summary: "Team meeting, weekly".into(),
+                },
+                CalendarEvent {
+                    start: local("2023-01-26T09:00:00+00:00"),
+                    end: local("2023-01-26T09:30:00+00:00"),
+                    summary: "Folded summary".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_ics_with_tzid() {
+        let content = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;TZID=America/New_York:20230123T090000\r\n\
+            DTEND;TZID=\"America/New_York\":20230123T100000\r\n\
+            SUMMARY:Call with New York\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART;TZID=W. Europe Standard Time:20230124T090000\r\n\
+            DTEND;TZID=W. Europe Standard Time:20230124T100000\r\n\
+            SUMMARY:Unknown zone\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let events = parse_ics(content, local("2024-01-01T00:00:00+00:00")).unwrap();
+        assert_eq!(events[0].start, local("2023-01-23T09:00:00-05:00"));
+        assert_eq!(events[0].end, local("2023-01-23T10:00:00-05:00"));
+        // read as local time
+        assert_eq!(events[1].start, at("2023-01-24T09:00:00"));
+    }
+
+    /// Local time, without offset
+    fn at(time: &str) -> chrono::DateTime<chrono::offset::Local> {
+        Local.from_local_datetime(&time.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_ics_recurring_events() {
+        let content = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:standup\r\n\
+            DTSTART:20230102T090000\r\n\
+            DTEND:20230102T091500\r\n\
+            RRULE:FREQ=WEEKLY;BYDAY=MO,WE;COUNT=5\r\n\
+            EXDATE:20230104T090000\r\n\
+            SUMMARY:Standup\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:standup\r\n\
+            RECURRENCE-ID:20230109T090000\r\n\
+            DTSTART:20230109T140000\r\n\
+            DTEND:20230109T141500\r\n\
+            SUMMARY:Standup moved\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:review\r\n\
+            DTSTART:20230116T160000\r\n\
+            DTEND:20230116T170000\r\n\
+            RRULE:FREQ=DAILY;INTERVAL=2;UNTIL=20230120\r\n\
+            SUMMARY:Review\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:monthly\r\n\
+            DTSTART:20230103T100000\r\n\
+            DTEND:20230103T110000\r\n\
+            RRULE:FREQ=MONTHLY\r\n\
+            SUMMARY:Monthly\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let event = |start: &str, end: &str, summary: &str| CalendarEvent {
+            start: at(start),
+            end: at(end),
+            summary: summary.into(),
+        };
+        let starts = |until: &str| -> Vec<String> {
+            parse_ics(content, at(until))
+                .unwrap()
+                .iter()
+                .map(|event| format!("{} {}", event.start.format("%F %H:%M"), event.summary))
+                .collect()
+        };
+        assert_eq!(
+            starts("2023-02-01T00:00:00"),
+            vec![
+                "2023-01-02 09:00 Standup",
+                "2023-01-11 09:00 Standup",
+                "2023-01-16 09:00 Standup",
+                "2023-01-09 14:00 Standup moved",
+                "2023-01-16 16:00 Review",
+                "2023-01-18 16:00 Review",
+                "2023-01-20 16:00 Review",
+                "2023-01-03 10:00 Monthly",
+            ]
+        );
+        // only occurrences which started before `until`:
+        assert_eq!(
+            starts("2023-01-16T08:00:00"),
+            vec![
+                "2023-01-02 09:00 Standup",
+                "2023-01-11 09:00 Standup",
+                "2023-01-09 14:00 Standup moved",
+                "2023-01-03 10:00 Monthly",
+            ]
+        );
+        assert_eq!(
+            parse_ics(content, at("2023-01-03T00:00:00")).unwrap()[0],
+            event("2023-01-02T09:00:00", "2023-01-02T09:15:00", "Standup")
+        );
+    }
+
+    #[test]
+    fn test_uncovered_parts() {
+        let db = Database::in_memory(
+            vec![
+                WorktimeEntry {
+                    start: local("2023-01-23T08:00:00+01:00"),
+                    end: local("2023-01-23T09:30:00+01:00"),
+                    comments: "".into(),
+                },
+                WorktimeEntry {
+                    start: local("2023-01-23T10:00:00+01:00"),
+                    end: local("2023-01-23T10:15:00+01:00"),
+                    comments: "".into(),
+                },
+            ],
+            vec![],
+        );
+        let event = CalendarEvent {
+            start: local("2023-01-23T09:00:00+01:00"),
+            end: local("2023-01-23T11:00:00+01:00"),
+            summary: "Meeting".into(),
+        };
+        let parts = uncovered_parts(&db, &[], &event);
+        assert_eq!(
+            parts,
+            vec![
+                WorktimeEntry {
+                    start: local("2023-01-23T09:30:00+01:00"),
+                    end: local("2023-01-23T10:00:00+01:00"),
+                    comments: "Meeting".into(),
+                },
+                WorktimeEntry {
+                    start: local("2023-01-23T10:15:00+01:00"),
+                    end: local("2023-01-23T11:00:00+01:00"),
+                    comments: "Meeting".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_gaps_leaves_out_running_session() {
+        let mut db = Database::in_memory(vec![], vec![]);
+        let events = vec![CalendarEvent {
+            start: local("2023-01-23T22:30:00+01:00"),
+            end: local("2023-01-23T23:30:00+01:00"),
+            summary: "Meeting".into(),
+        }];
+        // a session running since 23:00, which is not committed yet:
+        let in_progress = vec![WorktimeEntry {
+            start: local("2023-01-23T23:00:00+01:00"),
+            end: local("2023-01-24T00:00:00+01:00"),
+            comments: "".into(),
+        }];
+        let range = (local("2023-01-23T00:00:00+01:00"), local("2023-01-24T00:00:00+01:00"));
+        let added = fill_gaps(&mut db, &in_progress, &events, range);
+        let expected = vec![WorktimeEntry {
+            start: local("2023-01-23T22:30:00+01:00"),
+            end: local("2023-01-23T23:00:00+01:00"),
+            comments: "Meeting".into(),
+        }];
+        assert_eq!(added, expected);
+        assert_eq!(db.rows(), &expected[..]);
+    }
+}
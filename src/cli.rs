@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -8,4 +8,209 @@ pub struct Args {
     pub overtime: bool,
     #[clap(long, short, action)]
     pub daysums: Option<u64>,
-}
\ No newline at end of file
+    /// How durations are written (default: `duration_style` of the configuration)
+    #[arg(long, global = true, value_enum)]
+    pub duration_style: Option<DurationStyle>,
+    /// How durations are rounded to the shown unit (default: `duration_rounding` of the configuration)
+    #[arg(long, global = true, value_enum)]
+    pub duration_rounding: Option<DurationRounding>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Export worktime entries and special days of a date range
+    Export {
+        #[arg(value_enum)]
+        format: ExportFormat,
+        /// First day to export (inclusive), e.g. 2023-05-01
+        #[arg(long)]
+        from: chrono::NaiveDate,
+        /// Last day to export (inclusive), e.g. 2023-05-31
+        #[arg(long)]
+        to: chrono::NaiveDate,
+        /// Write into this file instead of stdout
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+        /// Export the recorded times, without the configured billing rounding (iCalendar is never rounded)
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Add worktime entries for calendar events which overlap breaks
+    ImportCalendar {
+        /// iCalendar file to read events from
+        file: std::path::PathBuf,
+        /// Add all found entries without asking
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Import worktime entries from other time trackers
+    Import {
+        #[arg(value_enum)]
+        format: ImportFormat,
+        /// Files to import
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+        /// Only print what would be imported, do not modify the data file
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Render a printable monthly timesheet as HTML
+    Timesheet {
+        /// Month of the timesheet, e.g. 2026-09
+        #[arg(long, value_parser = crate::timesheet::parse_month)]
+        month: chrono::NaiveDate,
+        /// Write into this file instead of stdout
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Print when today's and this week's expected worktime will be reached
+    Forecast {
+        /// Take the accumulated overtime into account
+        #[arg(long)]
+        with_overtime: bool,
+    },
+    /// Monitor without interactive output, e.g. as systemd service
+    Daemon {
+        /// Append messages to this file instead of stdout/stderr (the journal, when run by systemd)
+        #[arg(long)]
+        log_file: Option<std::path::PathBuf>,
+    },
+    /// Write a systemd user unit which runs the daemon
+    InstallService {
+        /// Let the service append messages to this file instead of the journal
+        #[arg(long)]
+        log_file: Option<std::path::PathBuf>,
+    },
+    /// Merge the data files of other computers. Time worked on several computers at once is only counted once
+    Merge {
+        /// Data files of other computers
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+        /// Host the files come from (default: the file name without extension)
+        #[arg(long)]
+        host: Option<String>,
+        /// Only print what would be merged, do not modify the data file
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Split stored entries crossing the day boundary (`day_boundary`, midnight by default) into one entry per day
+    SplitAtDayBoundary {
+        /// Only print which entries would be split, do not modify the data file
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show the time per application of each worktime entry of a day (needs `track_windows`)
+    Apps {
+        /// Day to show, today by default
+        #[arg(long)]
+        day: Option<chrono::NaiveDate>,
+        /// Comment the entries without comment by the window rules
+        #[arg(long)]
+        tag: bool,
+    },
+    /// Show the git commits made during each worktime entry
+    GitLog {
+        /// Local git repositories to read
+        #[arg(long = "repo", required = true)]
+        repos: Vec<std::path::PathBuf>,
+        /// First day (inclusive), 6 days before --to by default
+        #[arg(long)]
+        from: Option<chrono::NaiveDate>,
+        /// Last day (inclusive), today by default
+        #[arg(long)]
+        to: Option<chrono::NaiveDate>,
+        /// Include commits of other authors than the repository's user.email
+        #[arg(long)]
+        all_authors: bool,
+        /// Suggest comments for entries without comment from their commits
+        #[arg(long)]
+        suggest: bool,
+        /// Use all suggested comments without asking
+        #[arg(long, short, requires = "suggest")]
+        yes: bool,
+    },
+    /// Show the worktime of a day or a week
+    Report {
+        /// Day to show (or a day of the week to show), today by default
+        #[arg(long)]
+        day: Option<chrono::NaiveDate>,
+        /// Show the whole week instead of a day
+        #[arg(long)]
+        week: bool,
+        #[arg(long, value_enum, default_value = "auto")]
+        format: ReportFormat,
+        /// Write into this file instead of stdout
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Print a one-line summary for status bars and exit
+    Status {
+        #[arg(long, value_enum, default_value = "plain")]
+        bar: BarFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// iCalendar (RFC 5545), readable by most calendar applications
+    Ics,
+    /// Timeclock clock-in/clock-out lines, as read by hledger. Comments become accounts
+    Timeclock,
+    /// Timewarrior intervals. Comments become tags
+    Timew,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ReportFormat {
+    /// Colored text on a terminal, plain text otherwise or if NO_COLOR is set
+    Auto,
+    Plain,
+    /// Text with ANSI colors
+    Color,
+    Json,
+    Markdown,
+    Html,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum DurationStyle {
+    /// Hours, minutes and seconds, e.g. 7h45m0s
+    Hms,
+    /// Hours and minutes, e.g. 7:45
+    Clock,
+    /// Decimal hours, e.g. 7.75h
+    Decimal,
+    /// Industrial minutes (hundredths of an hour), e.g. 775im
+    Industrial,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum DurationRounding {
+    /// Towards zero
+    Down,
+    Nearest,
+    /// Away from zero
+    Up,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum BarFormat {
+    /// JSON for a waybar custom module
+    Waybar,
+    /// Full text, short text and color lines for i3blocks
+    I3blocks,
+    /// A single line, e.g. for polybar
+    Plain,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ImportFormat {
+    /// Timewarrior data files (~/.timewarrior/data/*.data)
+    Timew,
+    /// Toggl or Clockify detailed CSV export
+    Toggl,
+    /// hledger / Emacs timeclock file
+    Timeclock,
+}
@@ -0,0 +1,60 @@
+/// Wall-clock time may jump, monotonic time may not, so differences between both are jumps
+const JUMP_THRESHOLD_SECONDS: i64 = 30;
+
+/// Detects jumps of the wall clock, caused by system sleep (the monotonic clock stops while
+/// suspended), NTP adjustments or manual clock changes. Call `check` periodically.
+#[derive(Default)]
+pub struct ClockJumpDetector {
+    previous: Option<(std::time::Instant, chrono::DateTime<chrono::offset::Local>)>,
+}
+
+impl ClockJumpDetector {
+    /// Returns the wall-clock time right before the jump and the time it jumped to
+    pub fn check(
+        self: &mut Self,
+        monotonic: std::time::Instant,
+        wall: chrono::DateTime<chrono::offset::Local>,
+    ) -> Option<(chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>)> {
+        let previous = self.previous.replace((monotonic, wall));
+        let (previous_monotonic, previous_wall) = previous?;
+        let monotonic_elapsed = chrono::Duration::from_std(monotonic - previous_monotonic).unwrap();
+        let drift = (wall - previous_wall) - monotonic_elapsed;
+        if drift.num_seconds().abs() < JUMP_THRESHOLD_SECONDS {
+            return None;
+        }
+        Some((previous_wall + monotonic_elapsed, wall))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_jump_detector() {
+        let mut detector = ClockJumpDetector::default();
+        let monotonic = std::time::Instant::now();
+        let wall: chrono::DateTime<chrono::offset::Local> = "2023-05-03T18:00:00+02:00".parse().unwrap();
+        assert_eq!(detector.check(monotonic, wall), None);
+
+        let monotonic = monotonic + std::time::Duration::from_secs(2);
+        let wall = wall + chrono::Duration::seconds(3);
+        assert_eq!(detector.check(monotonic, wall), None);
+
+        // suspended over night: the monotonic clock only moved by 2 seconds
+        let monotonic = monotonic + std::time::Duration::from_secs(2);
+        let resumed: chrono::DateTime<chrono::offset::Local> = "2023-05-04T08:00:00+02:00".parse().unwrap();
+        assert_eq!(
+            detector.check(monotonic, resumed),
+            Some((wall + chrono::Duration::seconds(2), resumed))
+        );
+
+        // clock set back by an hour
+        let monotonic = monotonic + std::time::Duration::from_secs(2);
+        let set_back = resumed - chrono::Duration::hours(1);
+        assert_eq!(
+            detector.check(monotonic, set_back),
+            Some((resumed + chrono::Duration::seconds(2), set_back))
+        );
+    }
+}
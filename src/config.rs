@@ -1,6 +1,26 @@
 use serde::{Deserialize, Serialize};
+use crate::billing::{BillingRounding, BillingScope};
+use crate::duration::{DurationFormat, DurationStyle, Rounding};
+use crate::locale::Locale;
 
-#[derive(Serialize, Deserialize)]
+/// A break of `break_minutes` is required when working more than `after_minutes` a day
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BreakRule {
+    pub after_minutes: i64,
+    pub break_minutes: i64,
+}
+
+/// Windows whose class and title match the regular expressions get `comment`. `$1` etc. in the
+/// comment are replaced by the groups captured from the title.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WindowRule {
+    pub class: String,
+    pub title: String,
+    pub comment: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     pub timeout_minutes: u64,
     pub data_file: String,
@@ -9,6 +29,65 @@ pub struct Config {
     pub weekly_hours: i64,
     pub cutoff_day_overtime_hours: f64,
     pub cutoff_datetime: chrono::DateTime<chrono::offset::Local>,
+    pub calendar_file: String,
+    pub calendar_auto_fill: bool,
+    pub employee_name: String,
+    pub live_state_file: String,
+    pub notify_break_reminder: bool,
+    pub notify_break_after_minutes: i64,
+    pub notify_day_target: bool,
+    pub notify_day_limit: bool,
+    pub notify_day_limit_hours: f64,
+    pub notify_week_target: bool,
+    pub hooks: Vec<String>,
+    pub break_rules: Vec<BreakRule>,
+    pub host_name: String,
+    pub host_file: String,
+    pub sync_dir: String,
+    pub track_windows: bool,
+    pub app_file: String,
+    pub window_rules: Vec<WindowRule>,
+    pub split_at_day_boundary: bool,
+    pub day_boundary: chrono::NaiveTime,
+    pub duration_style: DurationStyle,
+    pub duration_rounding: Rounding,
+    pub billing_increment_minutes: i64,
+    pub billing_scope: BillingScope,
+    pub billing_rounding: Rounding,
+    pub billing_overtime: bool,
+    pub locale: Locale,
+}
+
+impl Config {
+    /// `host_name`, or the name of this computer if not configured
+    pub fn host(self: &Self) -> String {
+        if self.host_name.is_empty() {
+            crate::sync::local_host_name()
+        } else {
+            self.host_name.clone()
+        }
+    }
+
+    pub fn duration_format(self: &Self) -> DurationFormat {
+        DurationFormat {
+            style: self.duration_style,
+            rounding: self.duration_rounding,
+        }
+    }
+
+    /// The configured billing rounding, None if disabled
+    pub fn billing(self: &Self) -> Option<BillingRounding> {
+        (self.billing_increment_minutes > 0).then(|| BillingRounding {
+            scope: self.billing_scope,
+            increment: chrono::Duration::minutes(self.billing_increment_minutes),
+            rounding: self.billing_rounding,
+        })
+    }
+
+    /// The local time sessions are split at when storing them, if enabled
+    pub fn storage_day_boundary(self: &Self) -> Option<chrono::NaiveTime> {
+        self.split_at_day_boundary.then_some(self.day_boundary)
+    }
 }
 
 impl ::std::default::Default for Config {
@@ -21,6 +100,43 @@ impl ::std::default::Default for Config {
             weekly_hours: 30,
             cutoff_day_overtime_hours: 0.0,
             cutoff_datetime: "2023-05-01T00:00:00.00+02:00".parse().unwrap(),
+            calendar_file: "".into(),
+            calendar_auto_fill: false,
+            employee_name: "".into(),
+            live_state_file: "~/.worktime.live.json".into(),
+            notify_break_reminder: false,
+            notify_break_after_minutes: 180,
+            notify_day_target: false,
+            notify_day_limit: false,
+            notify_day_limit_hours: 10.0,
+            notify_week_target: false,
+            hooks: Vec::new(),
+            // German Arbeitszeitgesetz
+            break_rules: vec![
+                BreakRule {
+                    after_minutes: 6 * 60,
+                    break_minutes: 30,
+                },
+                BreakRule {
+                    after_minutes: 9 * 60,
+                    break_minutes: 45,
+                },
+            ],
+            host_name: "".into(),
+            host_file: "~/.worktime.hosts.csv".into(),
+            sync_dir: "".into(),
+            track_windows: false,
+            app_file: "~/.worktime.apps.csv".into(),
+            window_rules: Vec::new(),
+            split_at_day_boundary: false,
+            day_boundary: chrono::NaiveTime::MIN,
+            duration_style: DurationStyle::Hms,
+            duration_rounding: Rounding::Down,
+            billing_increment_minutes: 0,
+            billing_scope: BillingScope::Entry,
+            billing_rounding: Rounding::Up,
+            billing_overtime: false,
+            locale: Locale::En,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,135 @@
+/// Sends a state (e.g. `READY=1`) to systemd. Does nothing if not started by systemd
+/// with `Type=notify`.
+pub fn sd_notify(state: &str) -> Result<(), String> {
+    let Some(socket_path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(());
+    };
+    notify_socket(&socket_path, state).map_err(|e| format!("NOTIFY_SOCKET {:?}: {}", socket_path, e))
+}
+
+#[cfg(target_os = "linux")]
+fn notify_socket(socket_path: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::ffi::OsStrExt;
+    let socket = std::os::unix::net::UnixDatagram::unbound()?;
+    let bytes = socket_path.as_bytes();
+    // systemd usually uses an abstract socket, marked by a leading '@'
+    let address = match bytes.strip_prefix(b"@") {
+        Some(name) => std::os::unix::net::SocketAddr::from_abstract_name(name)?,
+        None => std::os::unix::net::SocketAddr::from_pathname(socket_path)?,
+    };
+    socket.send_to_addr(state.as_bytes(), &address)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn notify_socket(_socket_path: &std::ffi::OsStr, _state: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "sd_notify is only supported on linux"))
+}
+
+/// Whether systemd expects watchdog pings from this process
+pub fn watchdog_enabled() -> bool {
+    let usec_set = std::env::var("WATCHDOG_USEC").is_ok_and(|usec| usec.parse::<u64>().is_ok());
+    let for_this_process = std::env::var("WATCHDOG_PID").map_or(true, |pid| pid == std::process::id().to_string());
+    usec_set && for_this_process
+}
+
+/// Appends stdout and stderr to `log_file`, instead of the terminal or journal
+#[cfg(target_os = "linux")]
+pub fn redirect_output(log_file: &std::path::Path) -> Result<(), String> {
+    use std::io::Write;
+    use std::os::unix::io::AsRawFd;
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .map_err(|e| format!("{}: {}", log_file.display(), e))?;
+    std::io::stdout().flush().unwrap();
+    std::io::stderr().flush().unwrap();
+    for fd in [1, 2] {
+        if unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+            return Err(format!("{}: {}", log_file.display(), std::io::Error::last_os_error()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn redirect_output(_log_file: &std::path::Path) -> Result<(), String> {
+    Err("log files are only supported on linux, redirect the output instead".to_string())
+}
+
+/// Directory for systemd user units: `$XDG_CONFIG_HOME/systemd/user`, `~/.config/systemd/user` by default
+pub fn user_unit_dir(xdg_config_home: Option<std::ffi::OsString>) -> Result<std::path::PathBuf, String> {
+    let config_home = match xdg_config_home.filter(|dir| !dir.is_empty()) {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => expanduser::expanduser("~/.config").map_err(|e| e.to_string())?,
+    };
+    Ok(config_home.join("systemd").join("user"))
+}
+
+pub fn unit_file(executable: &std::path::Path, log_file: Option<&std::path::Path>) -> String {
+    let log_file_arg = match log_file {
+        Some(log_file) => format!(" --log-file {}", log_file.display()),
+        None => String::new(),
+    };
+    format!(
+        "[Unit]
+Description=worktime activity monitoring
+After=graphical-session.target
+
+[Service]
+Type=notify
+ExecStart={} daemon{}
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+WatchdogSec=60
+
+[Install]
+WantedBy=default.target
+",
+        executable.display(),
+        log_file_arg
+    )
+}
+
+/// Writes `worktime.service` into `unit_dir` and returns its path
+pub fn install_service(
+    unit_dir: &std::path::Path,
+    executable: &std::path::Path,
+    log_file: Option<&std::path::Path>,
+) -> Result<std::path::PathBuf, String> {
+    std::fs::create_dir_all(unit_dir).map_err(|e| format!("{}: {}", unit_dir.display(), e))?;
+    let path = unit_dir.join("worktime.service");
+    std::fs::write(&path, unit_file(executable, log_file)).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_service_into_xdg_config_home() {
+        let config_home = std::env::temp_dir().join(format!("worktime-test-{}", std::process::id()));
+        let unit_dir = user_unit_dir(Some(config_home.clone().into_os_string())).unwrap();
+        assert_eq!(unit_dir, config_home.join("systemd/user"));
+
+        let path = install_service(&unit_dir, std::path::Path::new("/usr/bin/worktime"), None).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&config_home).unwrap();
+
+        assert_eq!(path, config_home.join("systemd/user/worktime.service"));
+        assert!(content.contains("ExecStart=/usr/bin/worktime daemon\n"));
+        assert!(content.contains("Type=notify\n"));
+    }
+
+    #[test]
+    fn test_unit_file_with_log_file() {
+        let content = unit_file(
+            std::path::Path::new("/usr/bin/worktime"),
+            Some(std::path::Path::new("/home/me/worktime.log")),
+        );
+        assert!(content.contains("ExecStart=/usr/bin/worktime daemon --log-file /home/me/worktime.log\n"));
+    }
+}
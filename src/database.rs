@@ -1,36 +1,438 @@
 use std::error::Error;
 use chrono::Datelike;
-use chrono::Timelike;
-use chrono::{offset::TimeZone, Local};
+use crate::billing::BillingRounding;
+use crate::locale::Locale;
 use crate::models::{WorktimeEntry, SpecialDayEntry, SpecialDayType};
-use crate::utils::format_chrono_duration;
+use crate::utils::start_of_day;
+
+const NOT_FULLY_INITIALIZED: &str = "Note: Database could not be fully initialized. \
+    Continuing with partially initialized database. Could not read {}: {}";
 
 pub struct Database {
     path: std::path::PathBuf,
-    pub rows: Vec<WorktimeEntry>,
+    /// Only changed by the methods of Database, which keep the query index up to date.
+    rows: Vec<WorktimeEntry>,
+    /// sorted by day
     pub special_days: Vec<SpecialDayEntry>,
+    /// Index for range queries: positions of the rows sorted by start, and the latest end of
+    /// all rows up to each of these positions
+    start_order: Vec<usize>,
+    max_ends: Vec<chrono::DateTime<chrono::offset::Local>>,
     file_access_lock: named_lock::NamedLock,
+    /// Time ranges in which no worktime is recorded (e.g. while monitoring is paused).
+    /// An open range (None as end) lasts until it is closed. Only kept in memory.
+    excluded_ranges: Vec<(
+        chrono::DateTime<chrono::offset::Local>,
+        Option<chrono::DateTime<chrono::offset::Local>>,
+    )>,
+    /// start of the last committed session and the times of the rows it was stored as
+    last_commit: Option<(
+        chrono::DateTime<chrono::offset::Local>,
+        Vec<(chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>)>,
+    )>,
+    /// comment for a session which was not committed yet
+    pending_comment: Option<(chrono::DateTime<chrono::offset::Local>, String)>,
+    /// sessions which were split by a clock jump: original start and start of the part after the jump
+    restarted_sessions: Vec<(chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>)>,
+    /// if set, sessions are stored as separate rows per day, split at this local time
+    day_boundary: Option<chrono::NaiveTime>,
+    first_weekday: chrono::Weekday,
+    /// increased on every change of the rows, so values calculated from them can be cached
+    revision: u64,
 }
 
 impl Database {
-    /// if last element in database has same start time it is overwritten. otherwise new element is pushed
-    pub fn commit_worktime(self: &mut Self, entry: WorktimeEntry) {
-        if self.rows.len() > 0 && self.rows[self.rows.len() - 1].start == entry.start {
-            self.rows.pop();
+    /// if last element in database has same start time it is overwritten. otherwise new element is pushed.
+    /// Time inside excluded ranges is cut out of the entry, so it might be stored as several rows.
+    /// Comments of an overwritten entry are kept, if the new entry has none.
+    /// Sessions split by a clock jump are committed from the start after the jump.
+    /// With a day boundary set, sessions crossing it are stored as one row per day.
+    pub fn commit_worktime(self: &mut Self, mut entry: WorktimeEntry) {
+        entry.start = self.restarted_start(entry.start);
+        if entry.end <= entry.start {
+            return;
+        }
+        let mut overwritten = Vec::new();
+        match self.last_commit_rows(entry.start) {
+            Some(indices) => {
+                for index in indices.into_iter().rev() {
+                    overwritten.insert(0, self.rows.remove(index));
+                }
+            }
+            None => {
+                if self.rows.len() > 0 && self.rows[self.rows.len() - 1].start == entry.start {
+                    overwritten.push(self.rows.pop().unwrap());
+                }
+            }
+        }
+        if entry.comments.is_empty() {
+            if let Some(previous) = overwritten.into_iter().find(|x| !x.comments.is_empty()) {
+                entry.comments = previous.comments;
+            } else if let Some((_, comments)) =
+                self.pending_comment.take_if(|(start, _)| *start == entry.start)
+            {
+                entry.comments = comments;
+            }
+        }
+        let session_start = entry.start;
+        let pieces = self.stored_pieces(entry);
+        self.last_commit = Some((session_start, pieces.iter().map(|piece| (piece.start, piece.end)).collect()));
+        for piece in pieces {
+            let index = self.rows.partition_point(|x| x < &piece);
+            self.rows.insert(index, piece);
+        }
+        self.update_index();
+    }
+
+    /// Indices of the rows the last commit of `session_start` was stored as. Rows inserted in between
+    /// since (e.g. merged from other hosts) are not included. None if another session was committed
+    /// last or its rows were changed since.
+    fn last_commit_rows(self: &Self, session_start: chrono::DateTime<chrono::offset::Local>) -> Option<Vec<usize>> {
+        let (_, times) = self.last_commit.as_ref().filter(|(start, _)| *start == session_start)?;
+        let mut indices = Vec::with_capacity(times.len());
+        let mut search_end = self.rows.len();
+        for (start, end) in times.iter().rev() {
+            let index = self.rows[..search_end]
+                .iter()
+                .rposition(|row| row.start == *start && row.end == *end)?;
+            indices.push(index);
+            search_end = index;
+        }
+        indices.reverse();
+        Some(indices)
+    }
+
+    /// Sets the comment of a session, which might not be committed yet.
+    pub fn set_session_comment(self: &mut Self, session_start: chrono::DateTime<chrono::offset::Local>, comments: String) {
+        let session_start = self.restarted_start(session_start);
+        if let Some(indices) = self.last_commit_rows(session_start) {
+            for index in indices {
+                self.rows[index].comments = comments.clone();
+            }
+            return;
+        }
+        self.pending_comment = Some((session_start, comments));
+    }
+
+    /// Starts a range in which no worktime is recorded, lasting until end_excluded_range is called.
+    pub fn begin_excluded_range(self: &mut Self, start: chrono::DateTime<chrono::offset::Local>) {
+        self.excluded_ranges.push((start, None));
+    }
+
+    pub fn end_excluded_range(self: &mut Self, end: chrono::DateTime<chrono::offset::Local>) {
+        if let Some(range) = self.excluded_ranges.last_mut() {
+            if range.1.is_none() {
+                range.1 = Some(end);
+            }
+        }
+    }
+
+    pub fn is_in_open_excluded_range(self: &Self) -> bool {
+        matches!(self.excluded_ranges.last(), Some((_, None)))
+    }
+
+    /// Stores sessions split at `boundary` (e.g. midnight) from now on. None stores each session as one row.
+    pub fn set_day_boundary(self: &mut Self, boundary: Option<chrono::NaiveTime>) {
+        self.day_boundary = boundary;
+    }
+
+    /// Splits all stored rows crossing `boundary`, like commit_worktime does with a day boundary set.
+    /// Returns the number of rows which were split.
+    pub fn split_rows_at_day_boundary(self: &mut Self, boundary: chrono::NaiveTime) -> usize {
+        let mut split_count = 0;
+        let rows = std::mem::take(&mut self.rows);
+        for row in rows {
+            let pieces = row.clone().split_at_day_boundary(boundary);
+            if pieces.len() > 1 {
+                split_count += 1;
+                self.rows.extend(pieces);
+            } else {
+                self.rows.push(row);
+            }
+        }
+        self.last_commit = None;
+        self.update_index();
+        split_count
+    }
+
+    /// The rows an entry is stored as: without excluded ranges and split at the day boundary
+    fn stored_pieces(self: &Self, entry: WorktimeEntry) -> Vec<WorktimeEntry> {
+        let pieces = self.cut_excluded_ranges(entry);
+        match self.day_boundary {
+            Some(boundary) => pieces
+                .into_iter()
+                .flat_map(|piece| piece.split_at_day_boundary(boundary))
+                .collect(),
+            None => pieces,
+        }
+    }
+
+    /// Splits an entry into the pieces lying outside of all excluded ranges
+    fn cut_excluded_ranges(self: &Self, entry: WorktimeEntry) -> Vec<WorktimeEntry> {
+        let mut pieces = vec![entry];
+        for (excluded_start, excluded_end) in self.excluded_ranges.iter() {
+            let mut remaining = Vec::new();
+            for piece in pieces {
+                let overlaps = piece.end > *excluded_start
+                    && excluded_end.map_or(true, |excluded_end| piece.start < excluded_end);
+                if !overlaps {
+                    remaining.push(piece);
+                    continue;
+                }
+                if piece.start < *excluded_start {
+                    remaining.push(WorktimeEntry {
+                        start: piece.start,
+                        end: *excluded_start,
+                        comments: piece.comments.clone(),
+                    });
+                }
+                if let Some(excluded_end) = excluded_end {
+                    if piece.end > *excluded_end {
+                        remaining.push(WorktimeEntry {
+                            start: *excluded_end,
+                            end: piece.end,
+                            comments: piece.comments.clone(),
+                        });
+                    }
+                }
+            }
+            pieces = remaining;
+        }
+        pieces
+    }
+
+    /// The pieces of a not yet committed session (running until `now`) as they would be stored.
+    pub fn in_progress_pieces(
+        self: &Self,
+        session_start: chrono::DateTime<chrono::offset::Local>,
+        now: chrono::DateTime<chrono::offset::Local>,
+    ) -> Vec<WorktimeEntry> {
+        let session_start = self.restarted_start(session_start);
+        if now <= session_start {
+            return Vec::new();
+        }
+        let comments = match &self.pending_comment {
+            Some((start, comments)) if *start == session_start => comments.clone(),
+            _ => String::new(),
+        };
+        self.stored_pieces(WorktimeEntry {
+            start: session_start,
+            end: now,
+            comments,
+        })
+    }
+
+    /// Start of the part of a not yet committed session which is still running at `now`.
+    /// None if the session is currently inside an excluded range.
+    pub fn effective_session_start(
+        self: &Self,
+        session_start: chrono::DateTime<chrono::offset::Local>,
+        now: chrono::DateTime<chrono::offset::Local>,
+    ) -> Option<chrono::DateTime<chrono::offset::Local>> {
+        self.in_progress_pieces(session_start, now)
+            .pop()
+            .filter(|piece| piece.end == now)
+            .map(|piece| piece.start)
+    }
+
+    /// Start of the part of a session after its last clock jump
+    fn restarted_start(
+        self: &Self,
+        session_start: chrono::DateTime<chrono::offset::Local>,
+    ) -> chrono::DateTime<chrono::offset::Local> {
+        self.restarted_sessions
+            .iter()
+            .find(|(original_start, _)| *original_start == session_start)
+            .map_or(session_start, |(_, restarted_start)| *restarted_start)
+    }
+
+    /// Closes the running session at `jump_start`, when the wall clock jumped from `jump_start` to
+    /// `jump_end` (e.g. after system sleep). The session continues from `jump_end` on, with its comment.
+    /// If the clock was set back, it continues from `jump_start` on, so no time is recorded twice.
+    pub fn split_session_at_clock_jump(
+        self: &mut Self,
+        session_start: chrono::DateTime<chrono::offset::Local>,
+        jump_start: chrono::DateTime<chrono::offset::Local>,
+        jump_end: chrono::DateTime<chrono::offset::Local>,
+    ) {
+        let current_start = self.restarted_start(session_start);
+        self.commit_worktime(WorktimeEntry {
+            start: session_start,
+            end: jump_start,
+            comments: String::new(),
+        });
+        let comments = match self.last_commit_rows(current_start).and_then(|indices| indices.last().copied()) {
+            Some(index) => self.rows[index].comments.clone(),
+            None => self
+                .pending_comment
+                .take()
+                .filter(|(start, _)| *start == current_start)
+                .map(|(_, comments)| comments)
+                .unwrap_or_default(),
+        };
+        let restart = std::cmp::max(jump_start, jump_end);
+        self.restarted_sessions.retain(|(original_start, _)| *original_start != session_start);
+        self.restarted_sessions.push((session_start, restart));
+        if !comments.is_empty() {
+            self.pending_comment = Some((restart, comments));
+        }
+    }
+
+    /// inserts entry at its sorted position. Unlike commit_worktime, entry may lie anywhere in the past
+    pub fn insert_worktime(self: &mut Self, entry: WorktimeEntry) {
+        let index = self.rows.partition_point(|x| x < &entry);
+        self.rows.insert(index, entry);
+        self.update_index();
+    }
+
+    /// Closes a session which was still running when the previous monitoring process died.
+    /// A stored unfinished row of the session (starting at `session_start`) is extended until `end`,
+    /// or until the next row. Otherwise the part of the session after the rows it overlaps (e.g.
+    /// merged from another computer) is added. Returns the recovered entry, if anything changed.
+    pub fn recover_session(
+        self: &mut Self,
+        session_start: chrono::DateTime<chrono::offset::Local>,
+        end: chrono::DateTime<chrono::offset::Local>,
+    ) -> Option<WorktimeEntry> {
+        let first = self.start_order.partition_point(|i| self.rows[*i].start < session_start);
+        let own_row = self.start_order[first..]
+            .iter()
+            .take_while(|i| self.rows[**i].start == session_start)
+            .copied()
+            .last();
+        if let Some(i) = own_row {
+            let next_start = self.start_order[first..]
+                .iter()
+                .map(|i| self.rows[*i].start)
+                .find(|start| *start > session_start);
+            let end = next_start.map_or(end, |next_start| std::cmp::min(end, next_start));
+            if self.rows[i].end >= end {
+                return None;
+            }
+            self.rows[i].end = end;
+            let row = self.rows[i].clone();
+            self.update_index();
+            return Some(row);
+        }
+        // never start before the end of a stored entry:
+        let start = self
+            .overlapping_rows((session_start, end))
+            .fold(session_start, |start, row| std::cmp::max(start, row.end));
+        if start >= end {
+            return None;
+        }
+        let entry = WorktimeEntry {
+            start,
+            end,
+            comments: String::new(),
+        };
+        self.insert_worktime(entry.clone());
+        Some(entry)
+    }
+
+    /// Rebuilds the query index after times of rows changed. Rows are usually sorted already,
+    /// which makes this linear.
+    fn update_index(self: &mut Self) {
+        let mut start_order: Vec<usize> = (0..self.rows.len()).collect();
+        start_order.sort_by_key(|i| self.rows[*i].start);
+        let mut max_ends = Vec::with_capacity(start_order.len());
+        for i in start_order.iter() {
+            let end = match max_ends.last() {
+                Some(max_end) => std::cmp::max(*max_end, self.rows[*i].end),
+                None => self.rows[*i].end,
+            };
+            max_ends.push(end);
         }
-        self.rows.push(entry);
+        self.start_order = start_order;
+        self.max_ends = max_ends;
+        self.revision = self.revision + 1;
     }
 
+    /// All stored rows, in the order of the data file
+    pub fn rows(self: &Self) -> &[WorktimeEntry] {
+        &self.rows
+    }
+
+    /// Changes whenever rows are added, removed or moved (but not when only a comment is set)
+    pub fn revision(self: &Self) -> u64 {
+        self.revision
+    }
+
+    /// Sets the comment of the stored row equal to `entry`. Returns false if there is none.
+    pub fn set_comment(self: &mut Self, entry: &WorktimeEntry, comments: String) -> bool {
+        let first = self.start_order.partition_point(|i| self.rows[*i].start < entry.start);
+        let found = self.start_order[first..]
+            .iter()
+            .take_while(|i| self.rows[**i].start == entry.start)
+            .find(|i| self.rows[**i] == *entry)
+            .copied();
+        match found {
+            Some(i) => {
+                self.rows[i].comments = comments;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stored rows sharing some time with `range`, ordered by start and not cut at the range (unlike
+    /// `query`). An empty range overlaps nothing. Found by binary search, as `max_ends` never decreases.
+    pub fn overlapping_rows<'a>(
+        self: &'a Self,
+        range: (chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>),
+    ) -> impl Iterator<Item = &'a WorktimeEntry> + 'a {
+        let first = self.max_ends.partition_point(|end| *end <= range.0);
+        let last = self.start_order.partition_point(|i| self.rows[*i].start < range.1);
+        self.start_order[first..std::cmp::max(first, last)]
+            .iter()
+            .map(|i| &self.rows[*i])
+            .filter(move |row| std::cmp::max(row.start, range.0) < std::cmp::min(row.end, range.1))
+    }
+
+    /// Database which is not backed by any file (used by unittests and benchmarks)
+    pub fn in_memory(rows: Vec<WorktimeEntry>, mut special_days: Vec<SpecialDayEntry>) -> Self {
+        special_days.sort();
+        let mut db = Database {
+            rows: rows,
+            special_days: special_days,
+            start_order: Vec::new(),
+            max_ends: Vec::new(),
+            path: std::path::PathBuf::new(),
+            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
+            excluded_ranges: Vec::new(),
+            last_commit: None,
+            pending_comment: None,
+            restarted_sessions: Vec::new(),
+            day_boundary: None,
+            first_weekday: chrono::Weekday::Mon,
+            revision: 0,
+        };
+        db.update_index();
+        db
+    }
+
+    /// Loads the data file and the special days. `locale` is the language of the notes about files
+    /// which could not be read.
     pub fn init(
         path: std::path::PathBuf,
         path_special_days: std::path::PathBuf,
+        locale: Locale,
     ) -> Result<Self, String> {
         let file_access_lock = named_lock::NamedLock::create("worktime_file_access").unwrap();
         let mut db = Database {
             path: path.clone(),
             rows: Vec::new(),
             special_days: Vec::new(),
+            start_order: Vec::new(),
+            max_ends: Vec::new(),
             file_access_lock: file_access_lock,
+            excluded_ranges: Vec::new(),
+            last_commit: None,
+            pending_comment: None,
+            restarted_sessions: Vec::new(),
+            day_boundary: None,
+            first_weekday: chrono::Weekday::Mon,
+            revision: 0,
         };
         // load worktime:
         let mut read_error: Option<csv::Error> = None;
@@ -53,15 +455,16 @@ impl Database {
             }
         }
         if let Some(err) = read_error {
-            println!("Note: Database could not be fully initialized. Continuing with partially initialized database. Could not read {}: {}", path.display(), err);
+            eprintln!("{}", locale.format(NOT_FULLY_INITIALIZED, &[&path.display(), &err]));
             return Ok(db);
         }
         db.rows.sort();
+        db.update_index();
 
         // load special days:
         let rdr = csv::Reader::from_path(path_special_days.clone());
         if let Err(ref err) = rdr {
-            println!("Note: Database could not be fully initialized. Continuing with partially initialized database. Could not read {}: {}", path_special_days.display(), err);
+            eprintln!("{}", locale.format(NOT_FULLY_INITIALIZED, &[&path_special_days.display(), err]));
         }
         let mut rdr = rdr.unwrap();
         for result in rdr.deserialize() {
@@ -92,10 +495,6 @@ impl Database {
         Ok(())
     }
 
-    fn is_in_range<T: core::cmp::PartialOrd>(element: &T, start: &T, end: &T) -> bool {
-        element >= start && element < end
-    }
-
     pub fn query_special_days<'a>(
         self: &'a Self,
         range: (
@@ -105,14 +504,10 @@ impl Database {
     ) -> impl Iterator<Item = &SpecialDayEntry> + '_ {
         let first = range.0.clone();
         let second = range.1.clone();
-        let it = self.special_days.iter().filter(move |x| {
-            let naive_day = x
-                .day
-                .and_time(chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap());
-            let day = chrono::Local.from_local_datetime(&naive_day).unwrap();
-            Self::is_in_range(&day, &first, &second)
-        });
-        it
+        // special days are sorted, so their midnights are as well
+        let begin = self.special_days.partition_point(|x| start_of_day(x.day) < first);
+        let end = self.special_days.partition_point(|x| start_of_day(x.day) < second);
+        self.special_days[begin..std::cmp::max(begin, end)].iter()
     }
 
     pub fn query<'a>(
@@ -125,13 +520,7 @@ impl Database {
         let first = range.0.clone();
         let second = range.1.clone();
         let it = self
-            .rows
-            .iter()
-            .filter(move |x| {
-                let result = Self::is_in_range(&x.start, &first, &second)
-                    || Self::is_in_range(&x.end, &first, &second);
-                result
-            })
+            .overlapping_rows(range)
             .map::<WorktimeEntry, _>(move |x| {
                 if x.start >= first && x.end <= second {
                     // trivial case: entry is completely inside the searched range. Return it:
@@ -150,214 +539,89 @@ impl Database {
         it
     }
 
+    /// The day of `time`, from its midnight until the next midnight (exclusive)
     pub fn get_day_bounds(
         time: chrono::DateTime<chrono::offset::Local>,
     ) -> (
         chrono::DateTime<chrono::offset::Local>,
         chrono::DateTime<chrono::offset::Local>,
     ) {
-        let date = chrono::NaiveDate::from_ymd_opt(time.year(), time.month(), time.day()).unwrap();
-
-        let time_start = chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap();
-        let time_end = chrono::NaiveTime::from_hms_nano_opt(23, 59, 59, 999_999_999).unwrap();
-
-        let day_start = chrono::NaiveDateTime::new(date, time_start);
-        let day_end = chrono::NaiveDateTime::new(date, time_end);
-
-        let local_day_start = Local.from_local_datetime(&day_start).unwrap();
-        let local_day_end = Local.from_local_datetime(&day_end).unwrap();
-        (local_day_start, local_day_end)
+        let date = time.date_naive();
+        (start_of_day(date), start_of_day(date.succ_opt().unwrap()))
     }
 
+    /// The week of `time`, from midnight of its first day until the first day of the next week (exclusive)
     pub fn get_week_bounds(
+        self: &Self,
         time: chrono::DateTime<chrono::offset::Local>,
     ) -> (
         chrono::DateTime<chrono::offset::Local>,
         chrono::DateTime<chrono::offset::Local>,
     ) {
-        let week = time.iso_week();
-
-        let date_start =
-            chrono::NaiveDate::from_isoywd_opt(week.year(), week.week(), chrono::Weekday::Mon)
-                .unwrap();
-        let date_end =
-            chrono::NaiveDate::from_isoywd_opt(week.year(), week.week(), chrono::Weekday::Sun)
-                .unwrap();
-
-        let time_start = chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap();
-        let time_end = chrono::NaiveTime::from_hms_nano_opt(23, 59, 59, 999_999_999).unwrap();
-
-        let start = chrono::NaiveDateTime::new(date_start, time_start);
-        let end = chrono::NaiveDateTime::new(date_end, time_end);
-        let local_start = Local.from_local_datetime(&start).unwrap();
-        let local_end = Local.from_local_datetime(&end).unwrap();
-        (local_start, local_end)
+        let first_day = self.get_week_start(time.date_naive());
+        (start_of_day(first_day), start_of_day(first_day + chrono::Duration::days(7)))
     }
 
-    pub fn print_simple_summary(self: &Self) {
-        let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
-
-        let mut previous_entry: Option<WorktimeEntry> = None;
-        let mut day_sum = chrono::Duration::seconds(0);
-        for entry in self.query(Self::get_day_bounds(now)) {
-            if let Some(previous_entry) = previous_entry {
-                println!(
-                    " {} Pause: {} -> {}",
-                    format_chrono_duration(&(entry.start - previous_entry.end)),
-                    previous_entry.end.format("%T"),
-                    entry.start.format("%T"),
-                );
-            }
-            println!(
-                "{} Worked: {} -> {}",
-                format_chrono_duration(&entry.duration()),
-                entry.start.format("%T"),
-                entry.end.format("%T")
-            );
-            day_sum = day_sum + entry.duration();
-            previous_entry = Some(entry);
-        }
-
-        let mut week_sum = chrono::Duration::seconds(0);
-        for i in self.query(Self::get_week_bounds(now)) {
-            week_sum = week_sum + i.duration();
-        }
-
-        println!(
-            "Current: Day: {}, Week: {}",
-            format_chrono_duration(&day_sum),
-            format_chrono_duration(&week_sum),
-        );
+    /// The first day of the week containing `day`
+    pub fn get_week_start(self: &Self, day: chrono::NaiveDate) -> chrono::NaiveDate {
+        day.week(self.first_weekday).first_day()
     }
 
-    pub fn print_filler(start: chrono::DateTime<chrono::offset::Local>, end: chrono::DateTime<chrono::offset::Local>, marker: &str) {
-        // Print an X for every 15,30,45,00 minute hit between entry.start and entry.end (exclusive)
-        let mut mark_time = start;
-        // Round up to the next 15-min mark
-        let minute = mark_time.time().minute();
-        let add_minutes = (15 - (minute % 15)) % 15;
-        mark_time = mark_time + chrono::Duration::minutes(add_minutes as i64);
-        while mark_time < end {
-            println!("| {} {}", mark_time.format("%H:%M"), marker);
-            mark_time = mark_time + chrono::Duration::minutes(15);
-        }
+    /// Weeks start on `first_weekday` from now on, monday (ISO weeks) by default
+    pub fn set_first_weekday(self: &mut Self, first_weekday: chrono::Weekday) {
+        self.first_weekday = first_weekday;
     }
 
-    pub fn print_vertical_timeline(self: &Self) {
-        let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
-
-        let mut previous_entry: Option<WorktimeEntry> = None;
+    pub fn get_day_sum(self: &Self, day: chrono::DateTime<chrono::offset::Local>) -> chrono::Duration {
         let mut day_sum = chrono::Duration::seconds(0);
-        for entry in self.query(Self::get_day_bounds(now)) {
-            if let Some(previous_entry) = previous_entry {
-                // Grey for filler lines
-                print!("\x1b[38;5;250m");
-                Self::print_filler(previous_entry.end, entry.start, "");
-                print!("\x1b[0m");
-                // Green for start working
-                println!("\x1b[32m| {} Start working (after {} break)\x1b[0m", entry.start.format("%T"), format_chrono_duration(&(entry.start - previous_entry.end)));
-            } else {
-                // First entry of the day, no previous entry
-                println!("\x1b[32m| {} Start working\x1b[0m", entry.start.format("%T"));
-            }
-            // Green for X lines
-            print!("\x1b[32m");
-            Self::print_filler(entry.start, entry.end, "X");
-            println!("| {} Stopped working (after {})\x1b[0m", entry.end.format("%T"), format_chrono_duration(&entry.duration()));
+        for entry in self.query(Self::get_day_bounds(day)) {
             day_sum = day_sum + entry.duration();
-            previous_entry = Some(entry);
         }
-        print!("\x1b[0m");
-
-        let mut week_sum = chrono::Duration::seconds(0);
-        for i in self.query(Self::get_week_bounds(now)) {
-            week_sum = week_sum + i.duration();
-        }
-
-        println!(
-            "Current: Day: {}, Week: {}",
-            format_chrono_duration(&day_sum),
-            format_chrono_duration(&week_sum),
-        );
+        day_sum
     }
 
-    /// Like print_vertical_timeline but also shows the current in-progress session.
-    ///
-    /// # Arguments
-    /// * `current_session_start` - Start time of the current in-progress session (if any)
-    pub fn print_vertical_timeline_with_current(
-        &self,
-        current_session_start: Option<chrono::DateTime<chrono::Local>>,
-    ) {
-        let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
-
-        let mut previous_entry: Option<WorktimeEntry> = None;
-        let mut day_sum = chrono::Duration::seconds(0);
-        for entry in self.query(Self::get_day_bounds(now)) {
-            if let Some(previous_entry) = previous_entry {
-                print!("\x1b[38;5;250m");
-                Self::print_filler(previous_entry.end, entry.start, "");
-                print!("\x1b[0m");
-                println!("\x1b[32m| {} Start working (after {} break)\x1b[0m", entry.start.format("%T"), format_chrono_duration(&(entry.start - previous_entry.end)));
-            } else {
-                println!("\x1b[32m| {} Start working\x1b[0m", entry.start.format("%T"));
-            }
-            print!("\x1b[32m");
-            Self::print_filler(entry.start, entry.end, "X");
-            println!("| {} Stopped working (after {})\x1b[0m", entry.end.format("%T"), format_chrono_duration(&entry.duration()));
-            day_sum = day_sum + entry.duration();
-            previous_entry = Some(entry);
-        }
-        print!("\x1b[0m");
-
-        // Show current in-progress session
-        if let Some(session_start) = current_session_start {
-            let current_duration = now - session_start;
-            // Only show if session started today
-            let (day_start, _) = Self::get_day_bounds(now);
-            if session_start >= day_start {
-                if let Some(prev) = previous_entry {
-                    print!("\x1b[38;5;250m");
-                    Self::print_filler(prev.end, session_start, "");
-                    print!("\x1b[0m");
-                    println!("\x1b[32m| {} Start working (after {} break)\x1b[0m",
-                        session_start.format("%T"),
-                        format_chrono_duration(&(session_start - prev.end)));
-                } else {
-                    println!("\x1b[32m| {} Start working\x1b[0m", session_start.format("%T"));
+    /// Sum of worktime within `range`, including the not yet committed session started at `session_start`
+    pub fn get_sum_with_session(
+        self: &Self,
+        range: (chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>),
+        session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+        now: chrono::DateTime<chrono::offset::Local>,
+    ) -> chrono::Duration {
+        let mut sum = self
+            .query(range)
+            .fold(chrono::Duration::zero(), |sum, entry| sum + entry.duration());
+        if let Some(session_start) = session_start {
+            for piece in self.in_progress_pieces(session_start, now) {
+                let start = std::cmp::max(piece.start, range.0);
+                let end = std::cmp::min(piece.end, range.1);
+                if end > start {
+                    sum = sum + (end - start);
                 }
-                print!("\x1b[33m"); // Yellow for in-progress
-                Self::print_filler(session_start, now, "~");
-                println!("| {} Working... ({})\x1b[0m", now.format("%T"), format_chrono_duration(&current_duration));
-                day_sum = day_sum + current_duration;
-            }
-        }
-
-        let mut week_sum = chrono::Duration::seconds(0);
-        for i in self.query(Self::get_week_bounds(now)) {
-            week_sum = week_sum + i.duration();
-        }
-        // Add current session to week sum too
-        if let Some(session_start) = current_session_start {
-            let (week_start, _) = Self::get_week_bounds(now);
-            if session_start >= week_start {
-                week_sum = week_sum + (now - session_start);
             }
         }
+        sum
+    }
 
-        println!(
-            "Current: Day: {}, Week: {}",
-            format_chrono_duration(&day_sum),
-            format_chrono_duration(&week_sum),
-        );
+    pub fn get_special_day(self: &Self, day: chrono::NaiveDate) -> Option<&SpecialDayEntry> {
+        let index = self.special_days.partition_point(|sd| sd.day < day);
+        self.special_days.get(index).filter(|sd| sd.day == day)
     }
 
-    pub fn get_day_sum(self: &Self, day: chrono::DateTime<chrono::offset::Local>) -> chrono::Duration {
-        let mut day_sum = chrono::Duration::seconds(0);
-        for entry in self.query(Self::get_day_bounds(day)) {
-            day_sum = day_sum + entry.duration();
+    /// Worktime expected on a day: weekly_worktime/5 from monday to friday,
+    /// nothing on weekends and on Vacation, Sick or Holiday special days.
+    pub fn get_expected_day_worktime(
+        self: &Self,
+        day: chrono::NaiveDate,
+        weekly_worktime: chrono::Duration,
+    ) -> chrono::Duration {
+        let special = self.get_special_day(day).filter(|sd| {
+            matches!(sd.day_type, SpecialDayType::Vacation | SpecialDayType::Sick | SpecialDayType::Holiday)
+        });
+        match (day.weekday(), special) {
+            (chrono::Weekday::Sat | chrono::Weekday::Sun, _) => chrono::Duration::zero(),
+            (_, Some(_)) => chrono::Duration::zero(),
+            _ => weekly_worktime / 5,
         }
-        day_sum
     }
 
     pub fn get_day_sums(
@@ -369,8 +633,8 @@ impl Database {
         let mut result = Vec::new();
         for _ in 0..num_days {
             let bounds = Self::get_day_bounds(time);
-            result.push((bounds.1, self.get_day_sum(time)));
-            time -= chrono::Duration::hours(24);
+            result.push((bounds.0, self.get_day_sum(time)));
+            time = start_of_day(time.date_naive().pred_opt().unwrap());
         }
 
         result
@@ -385,6 +649,20 @@ impl Database {
             chrono::DateTime<chrono::offset::Local>,
             chrono::DateTime<chrono::offset::Local>,
         ),
+    ) -> chrono::Duration {
+        self.calculate_billed_overtime(weekly_worktime, range, None)
+    }
+
+    /// Like calculate_overtime, but counting the worktime of each day as billed by `billing`, if given
+    pub fn calculate_billed_overtime(
+        self: &Self,
+        weekly_worktime: chrono::Duration,
+        // including start day, excluding end day
+        range: (
+            chrono::DateTime<chrono::offset::Local>,
+            chrono::DateTime<chrono::offset::Local>,
+        ),
+        billing: Option<&BillingRounding>,
     ) -> chrono::Duration {
         let (start, end) = range;
 
@@ -395,8 +673,20 @@ impl Database {
         // (Will still calculate weekends correctly, though setting the expectation to work mo-fr)
         // i.e. expect each day mo-f: weekly_hours/5 h of work and expect sa-so 0h of work
         let mut total_hours = chrono::Duration::seconds(0);
-        for i in self.query((start_of_calculation, start_of_today)) {
-            total_hours = total_hours + i.duration();
+        match billing {
+            None => {
+                for i in self.query((start_of_calculation, start_of_today)) {
+                    total_hours = total_hours + i.duration();
+                }
+            }
+            Some(billing) => {
+                let mut day = start_of_calculation.date_naive();
+                while start_of_day(day) < start_of_today {
+                    let entries: Vec<WorktimeEntry> = self.query(Self::get_day_bounds(start_of_day(day))).collect();
+                    total_hours = total_hours + billing.billed(&entries);
+                    day = day.succ_opt().unwrap();
+                }
+            }
         }
 
         let range = (start_of_today - start_of_calculation).num_days();
@@ -448,12 +738,10 @@ mod tests {
 
     #[test]
     fn test_overtime_empty_db_empty_range() {
-        let db = Database {
-            rows: vec![],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        let db = Database::in_memory(
+            vec![],
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-05-01T00:00:00.00+02:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -468,12 +756,10 @@ mod tests {
 
     #[test]
     fn test_overtime_empty_db_valid_range_weekday() {
-        let db = Database {
-            rows: vec![],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        let db = Database::in_memory(
+            vec![],
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-05-02T00:00:00.00+02:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -489,12 +775,10 @@ mod tests {
 
     #[test]
     fn test_overtime_empty_db_valid_range_weekend() {
-        let db = Database {
-            rows: vec![],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        let db = Database::in_memory(
+            vec![],
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-05-05T00:00:00.00+02:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -510,12 +794,10 @@ mod tests {
 
     #[test]
     fn test_overtime_empty_db_valid_range_only_weekend() {
-        let db = Database {
-            rows: vec![],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        let db = Database::in_memory(
+            vec![],
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-05-06T00:00:00.00+02:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -531,12 +813,10 @@ mod tests {
 
     #[test]
     fn test_overtime_empty_db_valid_range_full_week() {
-        let db = Database {
-            rows: vec![],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        let db = Database::in_memory(
+            vec![],
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-05-17T00:00:00.00+02:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -552,12 +832,10 @@ mod tests {
 
     #[test]
     fn test_overtime_empty_db_valid_range_full_week_not_0_clock() {
-        let db = Database {
-            rows: vec![],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        let db = Database::in_memory(
+            vec![],
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-05-17T16:22:12.00+02:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -573,12 +851,10 @@ mod tests {
 
     #[test]
     fn test_overtime_empty_db_valid_range_full_year() {
-        let db = Database {
-            rows: vec![],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        let db = Database::in_memory(
+            vec![],
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-01-01T00:00:00.00+01:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -594,16 +870,14 @@ mod tests {
 
     #[test]
     fn test_overtime_single_entry_worked_sunday() {
-        let db = Database {
-            rows: vec![WorktimeEntry {
+        let db = Database::in_memory(
+            vec![WorktimeEntry {
                 start: "2023-01-01T00:00:00.00+01:00".parse().unwrap(),
                 end: "2023-01-01T01:00:00.00+01:00".parse().unwrap(),
                 comments: "".into(),
             }],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-01-01T00:00:00.00+01:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -619,16 +893,14 @@ mod tests {
 
     #[test]
     fn test_overtime_single_entry_worked_monday_full() {
-        let db = Database {
-            rows: vec![WorktimeEntry {
+        let db = Database::in_memory(
+            vec![WorktimeEntry {
                 start: "2023-01-02T08:00:00.00+01:00".parse().unwrap(),
                 end: "2023-01-02T16:00:00.00+01:00".parse().unwrap(),
                 comments: "".into(),
             }],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-01-01T00:00:00.00+01:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -644,8 +916,8 @@ mod tests {
 
     #[test]
     fn test_overtime_single_entry_worked_monday_overtime() {
-        let db = Database {
-            rows: vec![
+        let db = Database::in_memory(
+            vec![
                 WorktimeEntry {
                     start: "2023-01-02T08:00:00.00+01:00".parse().unwrap(),
                     end: "2023-01-02T16:00:00.00+01:00".parse().unwrap(),
@@ -657,10 +929,8 @@ mod tests {
                     comments: "".into(),
                 },
             ],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-01-01T00:00:00.00+01:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -674,10 +944,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_billed_overtime() {
+        let db = Database::in_memory(
+            vec![
+                WorktimeEntry {
+                    start: "2023-01-02T08:00:00.00+01:00".parse().unwrap(),
+                    end: "2023-01-02T16:00:00.00+01:00".parse().unwrap(),
+                    comments: "".into(),
+                },
+                WorktimeEntry {
+                    start: "2023-01-02T17:00:00.00+01:00".parse().unwrap(),
+                    end: "2023-01-02T17:15:00.00+01:00".parse().unwrap(),
+                    comments: "".into(),
+                },
+            ],
+            vec![],
+        );
+        let start: chrono::DateTime<chrono::offset::Local> =
+            "2023-01-01T00:00:00.00+01:00".parse().unwrap();
+        let end: chrono::DateTime<chrono::offset::Local> =
+            "2023-01-03T00:00:00.00+01:00".parse().unwrap();
+        let weekly_hours = chrono::Duration::hours(40);
+        let billing = |scope, rounding| BillingRounding {
+            scope,
+            increment: chrono::Duration::minutes(30),
+            rounding,
+        };
+
+        assert_eq!(
+            db.calculate_billed_overtime(weekly_hours, (start, end), Some(&billing(crate::billing::BillingScope::Entry, crate::duration::Rounding::Up))),
+            chrono::Duration::minutes(30)
+        );
+        assert_eq!(
+            db.calculate_billed_overtime(weekly_hours, (start, end), Some(&billing(crate::billing::BillingScope::Day, crate::duration::Rounding::Down))),
+            chrono::Duration::zero()
+        );
+        assert_eq!(
+            db.calculate_billed_overtime(weekly_hours, (start, end), None),
+            db.calculate_overtime(weekly_hours, (start, end))
+        );
+    }
+
     #[test]
     fn test_overtime_single_entry_worked_out_of_range() {
-        let db = Database {
-            rows: vec![
+        let db = Database::in_memory(
+            vec![
                 WorktimeEntry {
                     start: "2023-01-02T08:00:00.00+01:00".parse().unwrap(),
                     end: "2023-01-02T16:00:00.00+01:00".parse().unwrap(),
@@ -689,10 +1001,8 @@ mod tests {
                     comments: "".into(),
                 },
             ],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-01-01T00:00:00.00+01:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -708,8 +1018,8 @@ mod tests {
 
     #[test]
     fn test_overtime_single_entry_worked_over_midnight_and_a_wohle_year() {
-        let db = Database {
-            rows: vec![
+        let db = Database::in_memory(
+            vec![
                 WorktimeEntry {
                     start: "2022-01-02T08:00:00.00+01:00".parse().unwrap(),
                     end: "2023-01-01T08:00:00.00+01:00".parse().unwrap(),
@@ -721,10 +1031,8 @@ mod tests {
                     comments: "".into(),
                 },
             ],
-            special_days: vec![],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+            vec![],
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-01-01T00:00:00.00+01:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -740,19 +1048,17 @@ mod tests {
 
     #[test]
     fn test_overtime_special_day() {
-        let db = Database {
-            rows: vec![WorktimeEntry {
+        let db = Database::in_memory(
+            vec![WorktimeEntry {
                 start: "2023-01-01T00:00:00.00+01:00".parse().unwrap(),
                 end: "2023-01-01T01:00:00.00+01:00".parse().unwrap(),
                 comments: "".into(),
             }],
-            special_days: vec![SpecialDayEntry {
+            vec![SpecialDayEntry {
                 day: "2023-01-02".parse().unwrap(),
                 day_type: SpecialDayType::Vacation,
             }],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-01-01T00:00:00.00+01:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -768,13 +1074,13 @@ mod tests {
 
     #[test]
     fn test_overtime_special_day_sunday() {
-        let db = Database {
-            rows: vec![WorktimeEntry {
+        let db = Database::in_memory(
+            vec![WorktimeEntry {
                 start: "2023-01-01T00:00:00.00+01:00".parse().unwrap(),
                 end: "2023-01-01T01:00:00.00+01:00".parse().unwrap(),
                 comments: "".into(),
             }],
-            special_days: vec![
+            vec![
                 SpecialDayEntry {
                     day: "2023-01-01".parse().unwrap(),
                     day_type: SpecialDayType::Vacation,
@@ -784,9 +1090,7 @@ mod tests {
                     day_type: SpecialDayType::Vacation,
                 },
             ],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-01-01T00:00:00.00+01:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -802,13 +1106,13 @@ mod tests {
 
     #[test]
     fn test_overtime_special_day_out_range() {
-        let db = Database {
-            rows: vec![WorktimeEntry {
+        let db = Database::in_memory(
+            vec![WorktimeEntry {
                 start: "2023-01-01T00:00:00.00+01:00".parse().unwrap(),
                 end: "2023-01-01T01:00:00.00+01:00".parse().unwrap(),
                 comments: "".into(),
             }],
-            special_days: vec![
+            vec![
                 SpecialDayEntry {
                     day: "2022-12-20".parse().unwrap(),
                     day_type: SpecialDayType::Vacation,
@@ -822,9 +1126,7 @@ mod tests {
                     day_type: SpecialDayType::Vacation,
                 },
             ],
-            path: std::path::PathBuf::new(),
-            file_access_lock: named_lock::NamedLock::create("dummy").unwrap(),
-        };
+        );
         let start: chrono::DateTime<chrono::offset::Local> =
             "2023-01-01T00:00:00.00+01:00".parse().unwrap();
         let end: chrono::DateTime<chrono::offset::Local> =
@@ -837,4 +1139,523 @@ mod tests {
             chrono::Duration::hours(1)
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_commit_worktime_overwrites_unfinished_entry() {
+        let mut db = Database::in_memory(vec![], vec![]);
+        let start: chrono::DateTime<chrono::offset::Local> = "2023-01-02T08:00:00.00+01:00".parse().unwrap();
+        db.commit_worktime(WorktimeEntry {
+            start: start,
+            end: "2023-01-02T09:00:00.00+01:00".parse().unwrap(),
+            comments: "".into(),
+        });
+        db.set_session_comment(start, "coding".into());
+        db.commit_worktime(WorktimeEntry {
+            start: start,
+            end: "2023-01-02T10:00:00.00+01:00".parse().unwrap(),
+            comments: "".into(),
+        });
+
+        assert_eq!(
+            db.rows,
+            vec![WorktimeEntry {
+                start: start,
+                end: "2023-01-02T10:00:00.00+01:00".parse().unwrap(),
+                comments: "coding".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_commit_worktime_excluded_range() {
+        let mut db = Database::in_memory(vec![], vec![]);
+        let start: chrono::DateTime<chrono::offset::Local> = "2023-01-02T08:00:00.00+01:00".parse().unwrap();
+        db.set_session_comment(start, "coding".into());
+        db.begin_excluded_range("2023-01-02T09:00:00.00+01:00".parse().unwrap());
+        assert!(db.is_in_open_excluded_range());
+        assert_eq!(
+            db.effective_session_start(start, "2023-01-02T09:30:00.00+01:00".parse().unwrap()),
+            None
+        );
+        db.end_excluded_range("2023-01-02T10:00:00.00+01:00".parse().unwrap());
+        assert_eq!(
+            db.effective_session_start(start, "2023-01-02T10:30:00.00+01:00".parse().unwrap()),
+            Some("2023-01-02T10:00:00.00+01:00".parse().unwrap())
+        );
+
+        for end in ["2023-01-02T10:30:00.00+01:00", "2023-01-02T11:00:00.00+01:00"] {
+            db.commit_worktime(WorktimeEntry {
+                start: start,
+                end: end.parse().unwrap(),
+                comments: "".into(),
+            });
+        }
+
+        assert_eq!(
+            db.rows,
+            vec![
+                WorktimeEntry {
+                    start: start,
+                    end: "2023-01-02T09:00:00.00+01:00".parse().unwrap(),
+                    comments: "coding".into(),
+                },
+                WorktimeEntry {
+                    start: "2023-01-02T10:00:00.00+01:00".parse().unwrap(),
+                    end: "2023-01-02T11:00:00.00+01:00".parse().unwrap(),
+                    comments: "coding".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recover_session_extends_stored_row() {
+        let start: chrono::DateTime<chrono::offset::Local> = "2023-01-02T08:00:00.00+01:00".parse().unwrap();
+        let heartbeat: chrono::DateTime<chrono::offset::Local> = "2023-01-02T11:00:00.00+01:00".parse().unwrap();
+        let mut db = Database::in_memory(
+            vec![WorktimeEntry {
+                start: start,
+                end: "2023-01-02T10:30:00.00+01:00".parse().unwrap(),
+                comments: "coding".into(),
+            }],
+            vec![],
+        );
+        let recovered = WorktimeEntry {
+            start: start,
+            end: heartbeat,
+            comments: "coding".into(),
+        };
+        assert_eq!(db.recover_session(start, heartbeat), Some(recovered.clone()));
+        assert_eq!(db.rows, vec![recovered]);
+        // recovering twice changes nothing
+        assert_eq!(db.recover_session(start, heartbeat), None);
+    }
+
+    #[test]
+    fn test_recover_session_not_stored_yet() {
+        let mut db = Database::in_memory(
+            vec![WorktimeEntry {
+                start: "2023-01-02T07:00:00.00+01:00".parse().unwrap(),
+                end: "2023-01-02T08:10:00.00+01:00".parse().unwrap(),
+                comments: "".into(),
+            }],
+            vec![],
+        );
+        let recovered = db.recover_session(
+            "2023-01-02T08:00:00.00+01:00".parse().unwrap(),
+            "2023-01-02T09:00:00.00+01:00".parse().unwrap(),
+        );
+        assert_eq!(
+            recovered,
+            Some(WorktimeEntry {
+                start: "2023-01-02T08:10:00.00+01:00".parse().unwrap(),
+                end: "2023-01-02T09:00:00.00+01:00".parse().unwrap(),
+                comments: "".into(),
+            })
+        );
+        assert_eq!(db.rows.len(), 2);
+    }
+
+    #[test]
+    fn test_recover_session_after_foreign_row() {
+        let start: chrono::DateTime<chrono::offset::Local> = "2023-01-02T08:00:00.00+01:00".parse().unwrap();
+        let heartbeat: chrono::DateTime<chrono::offset::Local> = "2023-01-02T11:00:00.00+01:00".parse().unwrap();
+        // merged from another computer, sorting after the stored row of the session:
+        let foreign = WorktimeEntry {
+            start: "2023-01-02T09:00:00.00+01:00".parse().unwrap(),
+            end: "2023-01-02T09:30:00.00+01:00".parse().unwrap(),
+            comments: "other host".into(),
+        };
+        let own = WorktimeEntry {
+            start: start,
+            end: "2023-01-02T08:30:00.00+01:00".parse().unwrap(),
+            comments: "".into(),
+        };
+        let mut db = Database::in_memory(vec![own.clone(), foreign.clone()], vec![]);
+        let recovered = WorktimeEntry {
+            end: foreign.start,
+            ..own.clone()
+        };
+        assert_eq!(db.recover_session(start, heartbeat), Some(recovered.clone()));
+        assert_eq!(db.rows, vec![recovered, foreign.clone()]);
+
+        // without a stored row, only the part after the foreign row is added
+        let mut db = Database::in_memory(vec![foreign.clone()], vec![]);
+        let added = WorktimeEntry {
+            start: foreign.end,
+            end: heartbeat,
+            comments: "".into(),
+        };
+        assert_eq!(db.recover_session(start, heartbeat), Some(added.clone()));
+        assert_eq!(db.rows, vec![foreign, added]);
+    }
+
+    #[test]
+    fn test_split_session_at_clock_jump() {
+        let mut db = Database::in_memory(vec![], vec![]);
+        let start: chrono::DateTime<chrono::offset::Local> = "2023-01-02T17:00:00.00+01:00".parse().unwrap();
+        let suspended: chrono::DateTime<chrono::offset::Local> = "2023-01-02T18:00:00.00+01:00".parse().unwrap();
+        let resumed: chrono::DateTime<chrono::offset::Local> = "2023-01-03T08:00:00.00+01:00".parse().unwrap();
+        let end: chrono::DateTime<chrono::offset::Local> = "2023-01-03T09:00:00.00+01:00".parse().unwrap();
+        db.commit_worktime(WorktimeEntry {
+            start: start,
+            end: "2023-01-02T17:30:00.00+01:00".parse().unwrap(),
+            comments: "".into(),
+        });
+        db.set_session_comment(start, "coding".into());
+
+        db.split_session_at_clock_jump(start, suspended, resumed);
+        assert_eq!(db.effective_session_start(start, end), Some(resumed));
+
+        // the idle detector still reports the session with its original start
+        db.commit_worktime(WorktimeEntry {
+            start: start,
+            end: end,
+            comments: "".into(),
+        });
+        assert_eq!(
+            db.rows,
+            vec![
+                WorktimeEntry {
+                    start: start,
+                    end: suspended,
+                    comments: "coding".into(),
+                },
+                WorktimeEntry {
+                    start: resumed,
+                    end: end,
+                    comments: "coding".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_commit_keeps_rows_inserted_into_the_session() {
+        let at = |time: &str| -> chrono::DateTime<chrono::offset::Local> {
+            format!("2023-01-02T{}:00+01:00", time).parse().unwrap()
+        };
+        let entry = |start: &str, end: &str, comments: &str| WorktimeEntry {
+            start: at(start),
+            end: at(end),
+            comments: comments.into(),
+        };
+        let mut db = Database::in_memory(vec![entry("07:00", "07:30", "")], vec![]);
+        db.begin_excluded_range(at("09:00"));
+        db.end_excluded_range(at("10:00"));
+        db.commit_worktime(entry("08:00", "11:00", ""));
+        // e.g. merged from another host while the session is running
+        db.insert_worktime(entry("09:15", "09:45", "other host"));
+
+        db.set_session_comment(at("08:00"), "coding".into());
+        db.commit_worktime(entry("08:00", "12:00", ""));
+        assert_eq!(
+            db.rows,
+            vec![
+                entry("07:00", "07:30", ""),
+                entry("08:00", "09:00", "coding"),
+                entry("09:15", "09:45", "other host"),
+                entry("10:00", "12:00", "coding"),
+            ]
+        );
+        assert_eq!(db.query((at("09:00"), at("10:00"))).count(), 1);
+    }
+
+    #[test]
+    fn test_split_session_at_clock_set_back() {
+        let mut db = Database::in_memory(vec![], vec![]);
+        let start: chrono::DateTime<chrono::offset::Local> = "2023-01-02T08:00:00.00+01:00".parse().unwrap();
+        let jump_start: chrono::DateTime<chrono::offset::Local> = "2023-01-02T10:00:00.00+01:00".parse().unwrap();
+        let jump_end: chrono::DateTime<chrono::offset::Local> = "2023-01-02T09:00:00.00+01:00".parse().unwrap();
+        db.split_session_at_clock_jump(start, jump_start, jump_end);
+
+        // nothing runs until the clock reaches the time of the jump again
+        assert_eq!(db.effective_session_start(start, jump_end + chrono::Duration::minutes(30)), None);
+        let end = jump_start + chrono::Duration::hours(1);
+        assert_eq!(db.effective_session_start(start, end), Some(jump_start));
+        db.commit_worktime(WorktimeEntry {
+            start: start,
+            end: end,
+            comments: "".into(),
+        });
+        assert_eq!(
+            db.rows,
+            vec![
+                WorktimeEntry {
+                    start: start,
+                    end: jump_start,
+                    comments: "".into(),
+                },
+                WorktimeEntry {
+                    start: jump_start,
+                    end: end,
+                    comments: "".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_query_index_matches_linear_scan() {
+        let at = |minutes: i64| -> chrono::DateTime<chrono::offset::Local> {
+            let start: chrono::DateTime<chrono::offset::Local> = "2023-05-01T00:00:00+02:00".parse().unwrap();
+            start + chrono::Duration::minutes(minutes)
+        };
+        let entry = |start: i64, end: i64| WorktimeEntry {
+            start: at(start),
+            end: at(end),
+            comments: "".into(),
+        };
+        // a long entry followed by short ones, so ends are not sorted
+        let mut db = Database::in_memory(vec![entry(0, 600), entry(60, 90), entry(120, 130), entry(700, 800)], vec![]);
+        // committed after the clock was set back, so rows are not sorted by start either
+        db.commit_worktime(entry(30, 45));
+        db.insert_worktime(entry(1000, 1100));
+
+        for range_start in (-30..1200).step_by(15) {
+            for length in [0, 10, 30, 100, 1000] {
+                let range = (at(range_start), at(range_start + length));
+                let mut expected: Vec<WorktimeEntry> = db
+                    .rows
+                    .iter()
+                    .filter(|x| std::cmp::max(x.start, range.0) < std::cmp::min(x.end, range.1))
+                    .map(|x| WorktimeEntry {
+                        start: std::cmp::max(x.start, range.0),
+                        end: std::cmp::min(x.end, range.1),
+                        comments: x.comments.clone(),
+                    })
+                    .collect();
+                expected.sort();
+                let mut found: Vec<WorktimeEntry> = db.query(range).collect();
+                found.sort();
+                assert_eq!(found, expected, "range {:?}", range);
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_comment() {
+        let entry = |start: &str, end: &str| WorktimeEntry {
+            start: format!("2023-05-03T{}:00+02:00", start).parse().unwrap(),
+            end: format!("2023-05-03T{}:00+02:00", end).parse().unwrap(),
+            comments: "".into(),
+        };
+        let mut db = Database::in_memory(vec![entry("13:00", "14:00"), entry("08:00", "09:00"), entry("08:00", "10:00")], vec![]);
+        assert!(db.set_comment(&entry("08:00", "10:00"), "review".into()));
+        assert_eq!(db.rows()[2].comments, "review");
+        assert_eq!(db.rows()[1].comments, "");
+        assert!(!db.set_comment(&entry("08:00", "11:00"), "review".into()));
+        // the comment is part of the identity of the row
+        assert!(!db.set_comment(&entry("08:00", "10:00"), "meeting".into()));
+    }
+
+    #[test]
+    fn test_query_entry_spanning_the_range() {
+        let busy = WorktimeEntry {
+            start: "2023-03-24T18:00:00+01:00".parse().unwrap(),
+            end: "2023-03-28T08:00:00+02:00".parse().unwrap(),
+            comments: "busy".into(),
+        };
+        let db = Database::in_memory(vec![busy.clone()], vec![]);
+        // 2023-03-26 is the day DST starts in Europe
+        let day = start_of_day(chrono::NaiveDate::from_ymd_opt(2023, 3, 26).unwrap());
+        let bounds = Database::get_day_bounds(day);
+        assert_eq!(bounds.1, start_of_day(chrono::NaiveDate::from_ymd_opt(2023, 3, 27).unwrap()));
+        assert_eq!(
+            db.query(bounds).collect::<Vec<_>>(),
+            vec![WorktimeEntry {
+                start: bounds.0,
+                end: bounds.1,
+                comments: "busy".into(),
+            }]
+        );
+        assert_eq!(db.get_day_sum(day), bounds.1 - bounds.0);
+        // exports which need the stored row get it uncut:
+        assert_eq!(db.overlapping_rows(bounds).cloned().collect::<Vec<_>>(), vec![busy.clone()]);
+
+        // touching the range at either end is no overlap
+        assert_eq!(db.query((busy.end, busy.end + chrono::Duration::hours(1))).count(), 0);
+        assert_eq!(db.query((busy.start - chrono::Duration::hours(1), busy.start)).count(), 0);
+        assert_eq!(db.query((busy.start, busy.start)).count(), 0);
+    }
+
+    #[test]
+    fn test_week_bounds_with_first_weekday() {
+        let mut db = Database::in_memory(vec![], vec![]);
+        // a sunday
+        let day = chrono::NaiveDate::from_ymd_opt(2023, 5, 7).unwrap();
+        let monday = chrono::NaiveDate::from_ymd_opt(2023, 5, 1).unwrap();
+        assert_eq!(db.get_week_start(day), monday);
+        assert_eq!(
+            db.get_week_bounds(start_of_day(day)),
+            (start_of_day(monday), start_of_day(monday + chrono::Duration::days(7)))
+        );
+
+        db.set_first_weekday(chrono::Weekday::Sun);
+        assert_eq!(db.get_week_start(day), day);
+        assert_eq!(db.get_week_start(day - chrono::Duration::days(1)), day - chrono::Duration::days(7));
+        assert_eq!(
+            db.get_week_bounds(start_of_day(day) + chrono::Duration::hours(12)),
+            (start_of_day(day), start_of_day(day + chrono::Duration::days(7)))
+        );
+    }
+
+    #[test]
+    fn test_commit_splits_at_day_boundary() {
+        let at = |time: &str| -> chrono::DateTime<chrono::offset::Local> { time.parse().unwrap() };
+        let boundary = chrono::NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+        let mut db = Database::in_memory(vec![], vec![]);
+        db.set_day_boundary(Some(boundary));
+        let start = at("2023-01-02T22:00:00+01:00");
+        // auto-saves of the running session, before and after the boundary
+        db.commit_worktime(WorktimeEntry {
+            start,
+            end: at("2023-01-03T02:00:00+01:00"),
+            comments: "".into(),
+        });
+        assert_eq!(db.rows.len(), 1);
+        db.commit_worktime(WorktimeEntry {
+            start,
+            end: at("2023-01-03T05:00:00+01:00"),
+            comments: "".into(),
+        });
+        db.set_session_comment(start, "release".into());
+        assert_eq!(
+            db.rows,
+            vec![
+                WorktimeEntry {
+                    start,
+                    end: at("2023-01-03T04:00:00+01:00"),
+                    comments: "release".into(),
+                },
+                WorktimeEntry {
+                    start: at("2023-01-03T04:00:00+01:00"),
+                    end: at("2023-01-03T05:00:00+01:00"),
+                    comments: "release".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            db.in_progress_pieces(at("2023-01-03T03:00:00+01:00"), at("2023-01-03T04:30:00+01:00")).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_split_rows_at_day_boundary() {
+        let at = |time: &str| -> chrono::DateTime<chrono::offset::Local> { time.parse().unwrap() };
+        let entry = |start: &str, end: &str| WorktimeEntry {
+            start: at(start),
+            end: at(end),
+            comments: "busy".into(),
+        };
+        let mut db = Database::in_memory(
+            vec![
+                entry("2023-03-24T09:00:00+01:00", "2023-03-24T17:00:00+01:00"),
+                entry("2023-03-25T20:00:00+01:00", "2023-03-27T08:00:00+02:00"),
+            ],
+            vec![],
+        );
+        let before: Vec<WorktimeEntry> = db.query((at("2023-03-24T00:00:00+01:00"), at("2023-03-28T00:00:00+02:00"))).collect();
+        assert_eq!(db.split_rows_at_day_boundary(chrono::NaiveTime::MIN), 1);
+        assert_eq!(
+            db.rows,
+            vec![
+                entry("2023-03-24T09:00:00+01:00", "2023-03-24T17:00:00+01:00"),
+                entry("2023-03-25T20:00:00+01:00", "2023-03-26T00:00:00+01:00"),
+                entry("2023-03-26T00:00:00+01:00", "2023-03-27T00:00:00+02:00"),
+                entry("2023-03-27T00:00:00+02:00", "2023-03-27T08:00:00+02:00"),
+            ]
+        );
+        let after: Vec<WorktimeEntry> = db.query((at("2023-03-24T00:00:00+01:00"), at("2023-03-28T00:00:00+02:00"))).collect();
+        let sum = |entries: &[WorktimeEntry]| entries.iter().fold(chrono::Duration::zero(), |sum, x| sum + x.duration());
+        assert_eq!(sum(&before), sum(&after));
+        assert_eq!(db.split_rows_at_day_boundary(chrono::NaiveTime::MIN), 0);
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Entries and ranges are given in minutes after the midnight of one of these days
+        const DAYS: [&str; 3] = ["2023-03-25", "2023-05-01", "2023-10-28"];
+
+        fn at(day: &str, minutes: i64) -> chrono::DateTime<chrono::offset::Local> {
+            start_of_day(day.parse().unwrap()) + chrono::Duration::minutes(minutes)
+        }
+
+        fn database(day: &str, entries: &[(i64, i64)]) -> Database {
+            Database::in_memory(
+                entries
+                    .iter()
+                    .map(|(start, length)| WorktimeEntry {
+                        start: at(day, *start),
+                        end: at(day, start + length),
+                        comments: format!("{}", start),
+                    })
+                    .collect(),
+                vec![],
+            )
+        }
+
+        fn entries() -> impl Strategy<Value = Vec<(i64, i64)>> {
+            prop::collection::vec((-1000i64..5000, 1i64..3000), 0..20)
+        }
+
+        proptest! {
+            #[test]
+            fn query_cuts_overlapping_entries(
+                day in prop::sample::select(&DAYS[..]),
+                entries in entries(),
+                range_start in -1200i64..5200,
+                range_length in 0i64..3000,
+            ) {
+                let db = database(day, &entries);
+                let range = (at(day, range_start), at(day, range_start + range_length));
+
+                let mut expected: Vec<WorktimeEntry> = db
+                    .rows
+                    .iter()
+                    .filter(|x| std::cmp::max(x.start, range.0) < std::cmp::min(x.end, range.1))
+                    .map(|x| WorktimeEntry {
+                        start: std::cmp::max(x.start, range.0),
+                        end: std::cmp::min(x.end, range.1),
+                        comments: x.comments.clone(),
+                    })
+                    .collect();
+                expected.sort();
+                let mut found: Vec<WorktimeEntry> = db.query(range).collect();
+                found.sort();
+                prop_assert_eq!(&found, &expected);
+                for entry in found {
+                    prop_assert!(range.0 <= entry.start && entry.start < entry.end && entry.end <= range.1);
+                }
+            }
+
+            #[test]
+            fn day_sums_add_up_to_the_total(
+                day in prop::sample::select(&DAYS[..]),
+                entries in entries(),
+            ) {
+                let db = database(day, &entries);
+                let first: chrono::NaiveDate = day.parse().unwrap();
+                let dates: Vec<chrono::NaiveDate> = (-1..5).map(|i| first + chrono::Duration::days(i)).collect();
+
+                let mut sum = chrono::Duration::zero();
+                for date in dates.iter() {
+                    let bounds = Database::get_day_bounds(start_of_day(*date) + chrono::Duration::hours(12));
+                    prop_assert_eq!(bounds, (start_of_day(*date), start_of_day(date.succ_opt().unwrap())));
+                    sum = sum + db.get_day_sum(bounds.0);
+                }
+
+                let total = (start_of_day(dates[0]), start_of_day(dates[dates.len() - 1].succ_opt().unwrap()));
+                let clipped = db.rows.iter().fold(chrono::Duration::zero(), |sum, x| {
+                    let start = std::cmp::max(x.start, total.0);
+                    let end = std::cmp::min(x.end, total.1);
+                    if start < end { sum + (end - start) } else { sum }
+                });
+                prop_assert_eq!(sum, clipped);
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+/// How durations are written
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationStyle {
+    /// `7h45m0s`
+    Hms,
+    /// hours and minutes, `7:45`
+    Clock,
+    /// decimal hours, `7.75h`
+    Decimal,
+    /// industrial minutes (hundredths of an hour), `775im`
+    Industrial,
+}
+
+/// How a duration is rounded to the smallest unit its style shows. The amount is rounded, so
+/// `Down` means towards zero for negative durations as well.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rounding {
+    Down,
+    Nearest,
+    Up,
+}
+
+impl Rounding {
+    /// `amount` in multiples of `unit`
+    pub fn apply(self: Self, amount: i64, unit: i64) -> i64 {
+        match self {
+            Rounding::Down => amount / unit,
+            Rounding::Nearest => (amount + unit / 2) / unit,
+            Rounding::Up => (amount + unit - 1) / unit,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DurationFormat {
+    pub style: DurationStyle,
+    pub rounding: Rounding,
+}
+
+impl Default for DurationFormat {
+    fn default() -> Self {
+        DurationFormat {
+            style: DurationStyle::Hms,
+            rounding: Rounding::Down,
+        }
+    }
+}
+
+impl DurationFormat {
+    /// The smallest unit shown, in milliseconds
+    fn unit_millis(self: &Self) -> i64 {
+        match self.style {
+            DurationStyle::Hms => 1000,
+            DurationStyle::Clock => 60 * 1000,
+            DurationStyle::Decimal | DurationStyle::Industrial => 36 * 1000,
+        }
+    }
+
+    pub fn format(self: &Self, duration: &chrono::Duration) -> String {
+        let units = self.rounding.apply(duration.num_milliseconds().abs(), self.unit_millis());
+        // no sign if the duration is rounded to zero
+        let sign = if duration < &chrono::Duration::zero() && units > 0 { "-" } else { "" };
+        match self.style {
+            DurationStyle::Hms => format!("{}{}h{}m{}s", sign, units / 3600, units / 60 % 60, units % 60),
+            DurationStyle::Clock => format!("{}{}:{:02}", sign, units / 60, units % 60),
+            DurationStyle::Decimal => format!("{}{}.{:02}h", sign, units / 100, units % 100),
+            DurationStyle::Industrial => format!("{}{}im", sign, units),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(style: DurationStyle, rounding: Rounding, duration: chrono::Duration) -> String {
+        DurationFormat { style, rounding }.format(&duration)
+    }
+
+    #[test]
+    fn test_styles() {
+        let duration = chrono::Duration::minutes(7 * 60 + 45);
+        assert_eq!(format(DurationStyle::Hms, Rounding::Down, duration), "7h45m0s");
+        assert_eq!(format(DurationStyle::Clock, Rounding::Down, duration), "7:45");
+        assert_eq!(format(DurationStyle::Decimal, Rounding::Down, duration), "7.75h");
+        assert_eq!(format(DurationStyle::Industrial, Rounding::Down, duration), "775im");
+        assert_eq!(format(DurationStyle::Clock, Rounding::Down, chrono::Duration::minutes(5)), "0:05");
+        assert_eq!(format(DurationStyle::Decimal, Rounding::Down, chrono::Duration::minutes(3)), "0.05h");
+    }
+
+    #[test]
+    fn test_negative_durations_have_one_sign() {
+        let duration = -chrono::Duration::minutes(90);
+        assert_eq!(format(DurationStyle::Hms, Rounding::Down, duration), "-1h30m0s");
+        assert_eq!(format(DurationStyle::Clock, Rounding::Down, duration), "-1:30");
+        assert_eq!(format(DurationStyle::Decimal, Rounding::Down, duration), "-1.50h");
+        assert_eq!(format(DurationStyle::Industrial, Rounding::Down, duration), "-150im");
+        // rounded to zero
+        assert_eq!(format(DurationStyle::Clock, Rounding::Down, -chrono::Duration::seconds(59)), "0:00");
+        assert_eq!(format(DurationStyle::Hms, Rounding::Down, -chrono::Duration::milliseconds(500)), "0h0m0s");
+    }
+
+    #[test]
+    fn test_rounding() {
+        let duration = chrono::Duration::seconds(7 * 3600 + 29 * 60 + 30);
+        assert_eq!(format(DurationStyle::Clock, Rounding::Down, duration), "7:29");
+        assert_eq!(format(DurationStyle::Clock, Rounding::Nearest, duration), "7:30");
+        assert_eq!(format(DurationStyle::Clock, Rounding::Up, duration), "7:30");
+        assert_eq!(format(DurationStyle::Clock, Rounding::Up, -duration), "-7:30");
+        assert_eq!(format(DurationStyle::Clock, Rounding::Nearest, chrono::Duration::seconds(29)), "0:00");
+        // 7h29m30s are 7.4917h
+        assert_eq!(format(DurationStyle::Decimal, Rounding::Down, duration), "7.49h");
+        assert_eq!(format(DurationStyle::Decimal, Rounding::Nearest, duration), "7.49h");
+        assert_eq!(format(DurationStyle::Decimal, Rounding::Up, duration), "7.50h");
+        assert_eq!(format(DurationStyle::Hms, Rounding::Up, chrono::Duration::milliseconds(1500)), "0h0m2s");
+    }
+}
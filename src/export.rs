@@ -0,0 +1,277 @@
+use std::io::Write;
+use crate::billing::BillingRounding;
+use crate::database::Database;
+use crate::models::{SpecialDayEntry, WorktimeEntry};
+
+/// Escapes a TEXT value as described in RFC 5545 section 3.3.11
+fn escape_ics_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes a content line, folded after 75 octets (RFC 5545 section 3.1)
+fn write_ics_line<W: Write>(out: &mut W, line: &str) -> std::io::Result<()> {
+    let mut octets_in_line = 0;
+    for c in line.chars() {
+        if octets_in_line + c.len_utf8() > 75 {
+            out.write_all(b"\r\n ")?;
+            // the leading space of a continuation line counts as well
+            octets_in_line = 1;
+        }
+        write!(out, "{}", c)?;
+        octets_in_line += c.len_utf8();
+    }
+    out.write_all(b"\r\n")
+}
+
+fn format_ics_utc(time: &chrono::DateTime<chrono::offset::Local>) -> String {
+    time.with_timezone(&chrono::Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Entries of `range` to export. iCalendar events (`ics`) are whole stored rows and never billed,
+/// as billing would move the starts their UIDs are made of. Other formats get the entries cut at
+/// the range, billed with `billing` if given.
+pub fn export_entries(
+    db: &Database,
+    range: (chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>),
+    ics: bool,
+    billing: Option<BillingRounding>,
+) -> Vec<WorktimeEntry> {
+    if ics {
+        return db.overlapping_rows(range).cloned().collect();
+    }
+    match billing {
+        Some(billing) => billing.round_entries(db.query(range)),
+        None => db.query(range).collect(),
+    }
+}
+
+/// Writes worktime entries as VEVENTs and special days as all-day VEVENTs into
+/// a single VCALENDAR. Entries should be whole stored rows (see `Database::overlapping_rows`),
+/// as the UID of an event is made from its start.
+pub fn write_ics<'a, W: Write>(
+    out: &mut W,
+    entries: impl Iterator<Item = WorktimeEntry>,
+    special_days: impl Iterator<Item = &'a SpecialDayEntry>,
+) -> std::io::Result<()> {
+    let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+    let stamp = format_ics_utc(&now);
+
+    write_ics_line(out, "BEGIN:VCALENDAR")?;
+    write_ics_line(out, "VERSION:2.0")?;
+    write_ics_line(out, "PRODID:-//worktime//worktime//EN")?;
+    write_ics_line(out, "CALSCALE:GREGORIAN")?;
+    for entry in entries {
+        // the start of a stored row is unique within the database and does not change with the
+        // exported range, so it makes a stable UID. An entry cut at the range would get another one.
+        let start = format_ics_utc(&entry.start);
+        write_ics_line(out, "BEGIN:VEVENT")?;
+        write_ics_line(out, &format!("UID:worktime-{}", start))?;
+        write_ics_line(out, &format!("DTSTAMP:{}", stamp))?;
+        write_ics_line(out, &format!("DTSTART:{}", start))?;
+        write_ics_line(out, &format!("DTEND:{}", format_ics_utc(&entry.end)))?;
+        write_ics_line(out, "SUMMARY:Worktime")?;
+        if !entry.comments.is_empty() {
+            write_ics_line(out, &format!("DESCRIPTION:{}", escape_ics_text(&entry.comments)))?;
+        }
+        write_ics_line(out, "TRANSP:OPAQUE")?;
+        write_ics_line(out, "END:VEVENT")?;
+    }
+    for special_day in special_days {
+        let day = special_day.day.format("%Y%m%d");
+        write_ics_line(out, "BEGIN:VEVENT")?;
+        write_ics_line(out, &format!("UID:worktime-special-{}", day))?;
+        write_ics_line(out, &format!("DTSTAMP:{}", stamp))?;
+        write_ics_line(out, &format!("DTSTART;VALUE=DATE:{}", day))?;
+        write_ics_line(
+            out,
+            &format!("DTEND;VALUE=DATE:{}", special_day.day.succ_opt().unwrap().format("%Y%m%d")),
+        )?;
+        write_ics_line(out, &format!("SUMMARY:{:?}", special_day.day_type))?;
+        write_ics_line(out, "TRANSP:TRANSPARENT")?;
+        write_ics_line(out, "END:VEVENT")?;
+    }
+    write_ics_line(out, "END:VCALENDAR")?;
+    Ok(())
+}
+
+/// Writes worktime entries as clock-in/clock-out pairs of a timeclock file (as read by hledger).
+/// The comment becomes the account, entries without comment are booked on `work`.
+pub fn write_timeclock<W: Write>(
+    out: &mut W,
+    entries: impl Iterator<Item = WorktimeEntry>,
+) -> std::io::Result<()> {
+    for entry in entries {
+        // two spaces would start the description, a semicolon a comment:
+        let account = entry
+            .comments
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .replace(';', ",");
+        let account = if account.is_empty() { "work".to_string() } else { account };
+        writeln!(out, "i {} {}", entry.start.format("%Y/%m/%d %H:%M:%S"), account)?;
+        writeln!(out, "o {}", entry.end.format("%Y/%m/%d %H:%M:%S"))?;
+    }
+    Ok(())
+}
+
+/// Writes worktime entries as timewarrior intervals. The comment becomes the tag.
+pub fn write_timewarrior<W: Write>(
+    out: &mut W,
+    entries: impl Iterator<Item = WorktimeEntry>,
+) -> std::io::Result<()> {
+    for entry in entries {
+        let start = entry.start.with_timezone(&chrono::Utc).format("%Y%m%dT%H%M%SZ");
+        let end = entry.end.with_timezone(&chrono::Utc).format("%Y%m%dT%H%M%SZ");
+        let comment = entry.comments.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', " ");
+        if comment.trim().is_empty() {
+            writeln!(out, "inc {} - {}", start, end)?;
+        } else {
+            writeln!(out, "inc {} - {} # \"{}\"", start, end, comment)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SpecialDayType;
+
+    #[test]
+    fn test_escape_ics_text() {
+        assert_eq!(
+            escape_ics_text("Meetings, Some coding; a\\b\nnext"),
+            "Meetings\\, Some coding\\; a\\\\b\\nnext"
+        );
+    }
+
+    #[test]
+    fn test_fold_long_line() {
+        let mut out = Vec::new();
+        write_ics_line(&mut out, &format!("DESCRIPTION:{}", "x".repeat(100))).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), 75);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(lines[2], "");
+    }
+
+    #[test]
+    fn test_write_ics_entry_and_special_day() {
+        let entries = vec![WorktimeEntry {
+            start: "2023-01-23T08:39:43.00+01:00".parse().unwrap(),
+            end: "2023-01-23T10:52:18.00+01:00".parse().unwrap(),
+            comments: "Meetings, Some coding".into(),
+        }];
+        let special_days = vec![SpecialDayEntry {
+            day: "2023-05-29".parse().unwrap(),
+            day_type: SpecialDayType::Holiday,
+        }];
+        let mut out = Vec::new();
+        write_ics(&mut out, entries.into_iter(), special_days.iter()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(out.ends_with("END:VCALENDAR\r\n"));
+        assert!(out.contains("DTSTART:20230123T073943Z\r\n"));
+        assert!(out.contains("DTEND:20230123T095218Z\r\n"));
+        assert!(out.contains("DESCRIPTION:Meetings\\, Some coding\r\n"));
+        assert!(out.contains("DTSTART;VALUE=DATE:20230529\r\n"));
+        assert!(out.contains("DTEND;VALUE=DATE:20230530\r\n"));
+        assert!(out.contains("SUMMARY:Holiday\r\n"));
+    }
+
+    #[test]
+    fn test_billed_ics_export_keeps_uids() {
+        let db = Database::in_memory(
+            vec![
+                WorktimeEntry {
+                    start: "2023-05-03T08:00:00+02:00".parse().unwrap(),
+                    end: "2023-05-03T08:50:00+02:00".parse().unwrap(),
+                    comments: "".into(),
+                },
+                WorktimeEntry {
+                    start: "2023-05-03T08:55:00+02:00".parse().unwrap(),
+                    end: "2023-05-03T09:20:00+02:00".parse().unwrap(),
+                    comments: "".into(),
+                },
+            ],
+            Vec::new(),
+        );
+        let range = ("2023-05-03T00:00:00+02:00".parse().unwrap(), "2023-05-04T00:00:00+02:00".parse().unwrap());
+        let billing = BillingRounding {
+            scope: crate::billing::BillingScope::Entry,
+            increment: chrono::Duration::minutes(15),
+            rounding: crate::duration::Rounding::Up,
+        };
+        let uids = |billing: Option<BillingRounding>| {
+            let mut out = Vec::new();
+            write_ics(&mut out, export_entries(&db, range, true, billing).into_iter(), std::iter::empty()).unwrap();
+            String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with("UID:"))
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(uids(Some(billing)), uids(None));
+        assert_eq!(uids(None).len(), 2);
+        // the other formats are billed, which moves the second entry behind the rounded up first one:
+        let billed = export_entries(&db, range, false, Some(billing));
+        let nine: chrono::DateTime<chrono::offset::Local> = "2023-05-03T09:00:00+02:00".parse().unwrap();
+        assert_eq!(billed[1].start, nine);
+    }
+
+    fn sample_entries() -> Vec<WorktimeEntry> {
+        vec![
+            WorktimeEntry {
+                start: "2023-01-23T08:39:43.00+01:00".parse().unwrap(),
+                end: "2023-01-23T10:52:18.00+01:00".parse().unwrap(),
+                comments: "client:foo  review; \"fix\"".into(),
+            },
+            WorktimeEntry {
+                start: "2023-01-23T11:12:21.00+01:00".parse().unwrap(),
+                end: "2023-01-23T12:17:40.00+01:00".parse().unwrap(),
+                comments: "".into(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_write_timeclock() {
+        let mut out = Vec::new();
+        write_timeclock(&mut out, sample_entries().into_iter()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("i 2023/01/23 "));
+        assert!(lines[0].ends_with(" client:foo review, \"fix\""));
+        assert!(lines[1].starts_with("o 2023/01/23 "));
+        assert!(lines[2].ends_with(" work"));
+    }
+
+    #[test]
+    fn test_write_timewarrior() {
+        let mut out = Vec::new();
+        write_timewarrior(&mut out, sample_entries().into_iter()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "inc 20230123T073943Z - 20230123T095218Z # \"client:foo  review; \\\"fix\\\"\"\n\
+             inc 20230123T101221Z - 20230123T111740Z\n"
+        );
+    }
+}
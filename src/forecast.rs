@@ -0,0 +1,221 @@
+use crate::config::BreakRule;
+use crate::database::Database;
+use crate::locale::Locale;
+use crate::utils::{local_time_on, start_of_day};
+
+/// What is known about today and this week at `now`
+#[derive(Debug, PartialEq)]
+pub struct WorkFacts {
+    pub now: chrono::DateTime<chrono::offset::Local>,
+    pub worked_today: chrono::Duration,
+    /// breaks since the first worktime of today
+    pub breaks_today: chrono::Duration,
+    pub expected_today: chrono::Duration,
+    /// start of the first worktime of today
+    pub first_start: Option<chrono::DateTime<chrono::offset::Local>>,
+    pub worked_week: chrono::Duration,
+    pub expected_week: chrono::Duration,
+    /// expected worktime of the days after today in this week
+    pub expected_following_days: Vec<(chrono::NaiveDate, chrono::Duration)>,
+}
+
+impl WorkFacts {
+    /// `session_start` is the start of the running session, it is counted until `now`
+    pub fn collect(
+        db: &Database,
+        session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+        weekly_worktime: chrono::Duration,
+        now: chrono::DateTime<chrono::offset::Local>,
+    ) -> Self {
+        let today = now.date_naive();
+        let day_start = start_of_day(today);
+        let worked_today = db.get_sum_with_session((day_start, now), session_start, now);
+        let first_start = db
+            .query((day_start, now))
+            .map(|entry| std::cmp::max(entry.start, day_start))
+            .chain(session_start.map(|start| std::cmp::max(start, day_start)))
+            .min();
+        let breaks_today = match first_start {
+            Some(first_start) => (now - first_start) - worked_today,
+            None => chrono::Duration::zero(),
+        };
+
+        let first_day = db.get_week_start(today);
+        let week_days: Vec<_> = (0..7)
+            .map(|i| first_day + chrono::Duration::days(i))
+            .map(|day| (day, db.get_expected_day_worktime(day, weekly_worktime)))
+            .collect();
+        Self {
+            now,
+            worked_today,
+            breaks_today,
+            expected_today: db.get_expected_day_worktime(today, weekly_worktime),
+            first_start,
+            worked_week: db.get_sum_with_session((start_of_day(first_day), now), session_start, now),
+            expected_week: week_days.iter().fold(chrono::Duration::zero(), |sum, (_, expected)| sum + *expected),
+            expected_following_days: week_days.into_iter().filter(|(day, _)| *day > today).collect(),
+        }
+    }
+}
+
+/// Break required by the rules for the given worktime of a day
+pub fn required_break(rules: &[BreakRule], worked: chrono::Duration) -> chrono::Duration {
+    rules
+        .iter()
+        .filter(|rule| worked > chrono::Duration::minutes(rule.after_minutes))
+        .map(|rule| chrono::Duration::minutes(rule.break_minutes))
+        .max()
+        .unwrap_or_else(chrono::Duration::zero)
+}
+
+/// Time at which `remaining` worktime is done, when working from now on without further
+/// voluntary breaks. Statutory breaks which were not taken yet are added. None if nothing remains.
+pub fn balanced_at(
+    facts: &WorkFacts,
+    rules: &[BreakRule],
+    remaining: chrono::Duration,
+) -> Option<chrono::DateTime<chrono::offset::Local>> {
+    if remaining <= chrono::Duration::zero() {
+        return None;
+    }
+    let missing_break = required_break(rules, facts.worked_today + remaining) - facts.breaks_today;
+    Some(facts.now + remaining + std::cmp::max(missing_break, chrono::Duration::zero()))
+}
+
+/// Time at which `remaining` worktime of the week is done. What is still expected today is worked
+/// today, the rest on the following workdays of the week, each started at the time work started today
+/// and taking its expected worktime. The last workday takes all that is left.
+pub fn week_balanced_at(
+    facts: &WorkFacts,
+    rules: &[BreakRule],
+    remaining: chrono::Duration,
+) -> Option<chrono::DateTime<chrono::offset::Local>> {
+    let open_today = std::cmp::max(facts.expected_today - facts.worked_today, chrono::Duration::zero());
+    let workdays: Vec<_> = facts
+        .expected_following_days
+        .iter()
+        .filter(|(_, expected)| *expected > chrono::Duration::zero())
+        .collect();
+    if remaining <= open_today || workdays.is_empty() {
+        return balanced_at(facts, rules, remaining);
+    }
+    let start_time = facts.first_start.unwrap_or(facts.now).time();
+    let mut rest = remaining - open_today;
+    for (i, (day, expected)) in workdays.iter().enumerate() {
+        if rest <= *expected || i == workdays.len() - 1 {
+            return Some(local_time_on(*day, start_time) + rest + required_break(rules, rest));
+        }
+        rest = rest - *expected;
+    }
+    None
+}
+
+/// Clock time, with the weekday if it is not today
+pub fn format_clock_time(
+    time: chrono::DateTime<chrono::offset::Local>,
+    now: chrono::DateTime<chrono::offset::Local>,
+    locale: Locale,
+) -> String {
+    if time.date_naive() == now.date_naive() {
+        time.format("%H:%M").to_string()
+    } else {
+        format!("{} {}", locale.weekday(chrono::Datelike::weekday(&time)), time.format("%H:%M"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WorktimeEntry;
+
+    fn rules() -> Vec<BreakRule> {
+        vec![
+            BreakRule {
+                after_minutes: 6 * 60,
+                break_minutes: 30,
+            },
+            BreakRule {
+                after_minutes: 9 * 60,
+                break_minutes: 45,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_required_break() {
+        assert_eq!(required_break(&rules(), chrono::Duration::hours(6)), chrono::Duration::zero());
+        assert_eq!(required_break(&rules(), chrono::Duration::minutes(361)), chrono::Duration::minutes(30));
+        assert_eq!(required_break(&rules(), chrono::Duration::hours(10)), chrono::Duration::minutes(45));
+    }
+
+    #[test]
+    fn test_forecast_with_running_session() {
+        let now: chrono::DateTime<chrono::offset::Local> = "2023-05-03T12:00:00+02:00".parse().unwrap();
+        let db = Database::in_memory(
+            vec![
+                WorktimeEntry {
+                    start: "2023-05-01T08:00:00+02:00".parse().unwrap(),
+                    end: "2023-05-01T16:00:00+02:00".parse().unwrap(),
+                    comments: "".into(),
+                },
+                WorktimeEntry {
+                    start: "2023-05-02T08:00:00+02:00".parse().unwrap(),
+                    end: "2023-05-02T16:00:00+02:00".parse().unwrap(),
+                    comments: "".into(),
+                },
+                WorktimeEntry {
+                    start: "2023-05-03T08:00:00+02:00".parse().unwrap(),
+                    end: "2023-05-03T10:00:00+02:00".parse().unwrap(),
+                    comments: "".into(),
+                },
+            ],
+            vec![],
+        );
+        let session_start = Some("2023-05-03T10:10:00+02:00".parse().unwrap());
+        let facts = WorkFacts::collect(&db, session_start, chrono::Duration::hours(40), now);
+        assert_eq!(facts.worked_today, chrono::Duration::minutes(3 * 60 + 50));
+        assert_eq!(facts.breaks_today, chrono::Duration::minutes(10));
+        assert_eq!(facts.expected_today, chrono::Duration::hours(8));
+        assert_eq!(facts.worked_week, chrono::Duration::minutes(19 * 60 + 50));
+        assert_eq!(facts.expected_week, chrono::Duration::hours(40));
+
+        // 4h10m remaining, plus the 20 minutes of the 30 minutes break which are not taken yet
+        let remaining = facts.expected_today - facts.worked_today;
+        assert_eq!(
+            balanced_at(&facts, &rules(), remaining),
+            Some("2023-05-03T16:30:00+02:00".parse().unwrap())
+        );
+        assert_eq!(balanced_at(&facts, &rules(), chrono::Duration::zero()), None);
+
+        // 20h10m remaining: 4h10m today, 8h on Thursday and 8h on Friday, each started at 8:00
+        let remaining = facts.expected_week - facts.worked_week;
+        assert_eq!(
+            facts.expected_following_days.iter().map(|(day, _)| day.to_string()).collect::<Vec<_>>(),
+            vec!["2023-05-04", "2023-05-05", "2023-05-06", "2023-05-07"]
+        );
+        assert_eq!(
+            week_balanced_at(&facts, &rules(), remaining),
+            Some("2023-05-05T16:30:00+02:00".parse().unwrap())
+        );
+        assert_eq!(
+            week_balanced_at(&facts, &rules(), remaining - chrono::Duration::hours(14)),
+            Some("2023-05-04T10:00:00+02:00".parse().unwrap())
+        );
+        // Friday, the last workday, takes all that is left
+        assert_eq!(
+            week_balanced_at(&facts, &rules(), remaining + chrono::Duration::hours(2)),
+            Some("2023-05-05T18:45:00+02:00".parse().unwrap())
+        );
+        // done today
+        assert_eq!(
+            week_balanced_at(&facts, &rules(), chrono::Duration::hours(1)),
+            Some("2023-05-03T13:00:00+02:00".parse().unwrap())
+        );
+        assert_eq!(week_balanced_at(&facts, &rules(), chrono::Duration::zero()), None);
+
+        let friday = "2023-05-05T16:30:00+02:00".parse().unwrap();
+        assert_eq!(format_clock_time(friday, now, Locale::En), "Fri 16:30");
+        assert_eq!(format_clock_time(friday, now, Locale::De), "Fr 16:30");
+        assert_eq!(format_clock_time(now, now, Locale::De), "12:00");
+    }
+}
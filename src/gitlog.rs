@@ -0,0 +1,211 @@
+use crate::models::WorktimeEntry;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Commit {
+    /// name of the repository directory
+    pub repo: String,
+    pub hash: String,
+    /// author date, i.e. when the work was done, also after a rebase
+    pub time: chrono::DateTime<chrono::offset::Local>,
+    pub subject: String,
+}
+
+impl Commit {
+    pub fn short_hash(self: &Self) -> &str {
+        &self.hash[..std::cmp::min(7, self.hash.len())]
+    }
+}
+
+const LOG_FORMAT: &str = "--format=%H%x1f%aI%x1f%s";
+
+fn git(repo: &std::path::Path, args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| format!("git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("{}: {}", repo.display(), String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// `git log` output in `LOG_FORMAT`
+pub fn parse_log(output: &str, repo: &str) -> Result<Vec<Commit>, String> {
+    output
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let fields: Vec<&str> = line.splitn(3, '\x1f').collect();
+            let [hash, time, subject] = fields[..] else {
+                return Err(format!("unexpected git log line: {}", line));
+            };
+            let time = chrono::DateTime::parse_from_rfc3339(time).map_err(|e| format!("{}: {}", time, e))?;
+            Ok(Commit {
+                repo: repo.to_string(),
+                hash: hash.to_string(),
+                time: time.into(),
+                subject: subject.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The configured `user.email` of the repository
+pub fn configured_author(repo: &std::path::Path) -> Option<String> {
+    git(repo, &["config", "user.email"])
+        .ok()
+        .map(|email| email.trim().to_string())
+        .filter(|email| !email.is_empty())
+}
+
+/// Commits of all branches in `range`, oldest first. `author` filters by name or email.
+pub fn read_commits(
+    repo: &std::path::Path,
+    range: (chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>),
+    author: Option<&str>,
+) -> Result<Vec<Commit>, String> {
+    let since = format!("--since={}", range.0.to_rfc3339());
+    let until = format!("--until={}", range.1.to_rfc3339());
+    let mut args = vec!["log", "--all", "--no-merges", "--reverse", LOG_FORMAT, since.as_str(), until.as_str()];
+    let author_arg = author.map(|author| format!("--author={}", author));
+    if let Some(author_arg) = &author_arg {
+        args.push(author_arg.as_str());
+    }
+    let output = git(repo, &args)?;
+    let toplevel = git(repo, &["rev-parse", "--show-toplevel"])?;
+    let name = std::path::Path::new(toplevel.trim())
+        .file_name()
+        .map_or_else(|| toplevel.trim().to_string(), |name| name.to_string_lossy().to_string());
+    let mut commits = parse_log(&output, &name)?;
+    // --since/--until use the committer date, but commits are assigned by the author date
+    commits.retain(|commit| range.0 <= commit.time && commit.time < range.1);
+    Ok(commits)
+}
+
+/// Assigns each commit to the entry it falls into. Returns the entries with their commits and
+/// the commits made outside of all entries.
+pub fn attach(rows: &[WorktimeEntry], commits: &[Commit]) -> (Vec<(WorktimeEntry, Vec<Commit>)>, Vec<Commit>) {
+    let mut entries: Vec<(WorktimeEntry, Vec<Commit>)> = rows.iter().map(|row| (row.clone(), Vec::new())).collect();
+    let mut outside = Vec::new();
+    let mut commits = commits.to_vec();
+    commits.sort_by_key(|commit| commit.time);
+    for commit in commits {
+        match entries
+            .iter_mut()
+            .find(|(entry, _)| entry.start <= commit.time && commit.time <= entry.end)
+        {
+            Some((_, entry_commits)) => entry_commits.push(commit),
+            None => outside.push(commit),
+        }
+    }
+    (entries, outside)
+}
+
+/// Comment for a session from its commits, e.g. `worktime: Add git-log; Fix overtime`
+pub fn suggest_comment(commits: &[Commit]) -> Option<String> {
+    let mut repos: Vec<(&str, Vec<&str>)> = Vec::new();
+    for commit in commits {
+        match repos.iter_mut().find(|(repo, _)| *repo == commit.repo) {
+            Some((_, subjects)) => subjects.push(&commit.subject),
+            None => repos.push((&commit.repo, vec![&commit.subject])),
+        }
+    }
+    if repos.is_empty() {
+        return None;
+    }
+    Some(
+        repos
+            .iter()
+            .map(|(repo, subjects)| format!("{}: {}", repo, subjects.join("; ")))
+            .collect::<Vec<_>>()
+            .join(" | "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> chrono::DateTime<chrono::offset::Local> {
+        format!("2023-05-03T{}:00+02:00", time).parse().unwrap()
+    }
+
+    fn commit(repo: &str, time: &str, subject: &str) -> Commit {
+        Commit {
+            repo: repo.into(),
+            hash: format!("{:0>40}", subject.len()),
+            time: at(time),
+            subject: subject.into(),
+        }
+    }
+
+    #[test]
+    fn test_attach_and_suggest() {
+        let rows = vec![
+            WorktimeEntry {
+                start: at("09:00"),
+                end: at("11:00"),
+                comments: "".into(),
+            },
+            WorktimeEntry {
+                start: at("13:00"),
+                end: at("15:00"),
+                comments: "".into(),
+            },
+        ];
+        let commits = vec![
+            commit("worktime", "10:30", "Fix overtime"),
+            commit("worktime", "09:15", "Add git-log"),
+            commit("website", "11:00", "Update docs"),
+            commit("worktime", "12:00", "Lunch break fix"),
+        ];
+        let (entries, outside) = attach(&rows, &commits);
+        assert_eq!(entries[0].1.len(), 3);
+        assert_eq!(entries[1].1, vec![]);
+        assert_eq!(outside, vec![commit("worktime", "12:00", "Lunch break fix")]);
+
+        assert_eq!(
+            suggest_comment(&entries[0].1),
+            Some("worktime: Add git-log; Fix overtime | website: Update docs".into())
+        );
+        assert_eq!(suggest_comment(&entries[1].1), None);
+    }
+
+    #[test]
+    fn test_read_commits_of_repository() {
+        let repo = std::env::temp_dir().join(format!("worktime-git-test-{}", std::process::id()));
+        std::fs::create_dir_all(&repo).unwrap();
+        let run = |args: &[&str], date: &str| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(args)
+                .env("GIT_AUTHOR_DATE", date)
+                .env("GIT_COMMITTER_DATE", date)
+                .env("GIT_AUTHOR_NAME", "Me")
+                .env("GIT_AUTHOR_EMAIL", "me@example.com")
+                .env("GIT_COMMITTER_NAME", "Me")
+                .env("GIT_COMMITTER_EMAIL", "me@example.com")
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        run(&["init", "-q"], "");
+        run(&["commit", "-q", "--allow-empty", "-m", "Yesterday"], "2023-05-02T18:00:00+02:00");
+        run(&["commit", "-q", "--allow-empty", "-m", "First"], "2023-05-03T09:30:00+02:00");
+        run(&["commit", "-q", "--allow-empty", "-m", "Second"], "2023-05-03T10:00:00+02:00");
+
+        let commits = read_commits(&repo, (at("00:00"), at("23:59")), Some("me@example.com")).unwrap();
+        let other_author = read_commits(&repo, (at("00:00"), at("23:59")), Some("other@example.com")).unwrap();
+        std::fs::remove_dir_all(&repo).unwrap();
+
+        let subjects: Vec<&str> = commits.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(subjects, vec!["First", "Second"]);
+        assert_eq!(commits[0].time, at("09:30"));
+        assert_eq!(commits[0].repo, repo.file_name().unwrap().to_string_lossy());
+        assert_eq!(commits[0].short_hash().len(), 7);
+        assert_eq!(other_author, vec![]);
+    }
+}
@@ -0,0 +1,283 @@
+use std::sync::mpsc;
+use crate::database::Database;
+
+/// Events passed to the user-defined hook executables
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    SessionStarted {
+        start: chrono::DateTime<chrono::offset::Local>,
+    },
+    SessionClosed {
+        start: chrono::DateTime<chrono::offset::Local>,
+        end: chrono::DateTime<chrono::offset::Local>,
+        /// worktime of the session, without paused time
+        duration: chrono::Duration,
+    },
+    DayTargetReached {
+        day_sum: chrono::Duration,
+        expected: chrono::Duration,
+    },
+    AutoSaved,
+}
+
+impl Event {
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            Event::SessionStarted { .. } => "session_started",
+            Event::SessionClosed { .. } => "session_closed",
+            Event::DayTargetReached { .. } => "day_target_reached",
+            Event::AutoSaved => "auto_saved",
+        }
+    }
+
+    /// Environment variables describing the event. Times are RFC 3339, durations in seconds.
+    pub fn env_vars(self: &Self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![("WORKTIME_EVENT", self.name().to_string())];
+        match self {
+            Event::SessionStarted { start } => {
+                vars.push(("WORKTIME_SESSION_START", start.to_rfc3339()));
+            }
+            Event::SessionClosed { start, end, duration } => {
+                vars.push(("WORKTIME_SESSION_START", start.to_rfc3339()));
+                vars.push(("WORKTIME_SESSION_END", end.to_rfc3339()));
+                vars.push(("WORKTIME_SESSION_DURATION", duration.num_seconds().to_string()));
+            }
+            Event::DayTargetReached { day_sum, expected } => {
+                vars.push(("WORKTIME_DAY_SUM", day_sum.num_seconds().to_string()));
+                vars.push(("WORKTIME_DAY_EXPECTED", expected.num_seconds().to_string()));
+            }
+            Event::AutoSaved => (),
+        }
+        vars
+    }
+}
+
+/// Runs all hooks one after another and waits for them. Returns a message for every failed hook.
+/// Without `show_stderr` the error output of the hooks is discarded, e.g. while the terminal UI is drawn.
+pub fn run_all(hooks: &[String], event: &Event, show_stderr: bool) -> Vec<String> {
+    let mut errors = Vec::new();
+    for hook in hooks {
+        let path = expanduser::expanduser(hook).unwrap_or_else(|_| std::path::PathBuf::from(hook));
+        let result = std::process::Command::new(&path)
+            .envs(event.env_vars())
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(if show_stderr { std::process::Stdio::inherit() } else { std::process::Stdio::null() })
+            .status();
+        match result {
+            Ok(status) if status.success() => (),
+            Ok(status) => errors.push(format!("hook {} failed on {}: {}", hook, event.name(), status)),
+            Err(err) => errors.push(format!("hook {} failed on {}: {}", hook, event.name(), err)),
+        }
+    }
+    errors
+}
+
+/// Runs the hooks of the queued events in the background, so slow hooks do not block monitoring.
+/// Events are handled one after another in the order they were fired.
+pub struct HookWorker {
+    queue: HookQueue,
+    thread: std::thread::JoinHandle<()>,
+}
+
+/// Fires events to the hook worker, can be cloned for every monitoring thread
+#[derive(Clone)]
+pub struct HookQueue {
+    sender: mpsc::Sender<(Vec<String>, Event)>,
+}
+
+impl HookWorker {
+    /// Errors and the error output of the hooks are only shown with `report_errors`
+    pub fn start(report_errors: bool) -> Self {
+        let (sender, receiver) = mpsc::channel::<(Vec<String>, Event)>();
+        let thread = std::thread::spawn(move || {
+            for (hooks, event) in receiver {
+                for error in run_all(&hooks, &event, report_errors) {
+                    if report_errors {
+                        eprintln!("{}", error);
+                    }
+                }
+            }
+        });
+        HookWorker {
+            queue: HookQueue { sender },
+            thread,
+        }
+    }
+
+    pub fn queue(self: &Self) -> HookQueue {
+        self.queue.clone()
+    }
+
+    /// Runs the remaining events and ends the thread. Clones of the queue must be dropped before.
+    pub fn stop(self: Self) {
+        drop(self.queue);
+        self.thread.join().unwrap();
+    }
+}
+
+impl HookQueue {
+    pub fn fire(self: &Self, hooks: &[String], event: Event) {
+        if !hooks.is_empty() {
+            let _ = self.sender.send((hooks.to_vec(), event));
+        }
+    }
+}
+
+/// Derives session and day target events from periodic polls of the idle detector
+#[derive(Default)]
+pub struct EventTracker {
+    session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+    day_target_reached: Option<chrono::NaiveDate>,
+}
+
+impl EventTracker {
+    pub fn update(
+        self: &mut Self,
+        db: &Database,
+        session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+        day_sum: chrono::Duration,
+        day_expected: chrono::Duration,
+        now: chrono::DateTime<chrono::offset::Local>,
+    ) -> Vec<Event> {
+        let mut events = Vec::new();
+        if session_start != self.session_start {
+            if let Some(previous_start) = self.session_start {
+                events.push(Self::closed_session(db, previous_start, now));
+            }
+            if let Some(start) = session_start {
+                events.push(Event::SessionStarted { start });
+            }
+            self.session_start = session_start;
+        }
+        let today = now.date_naive();
+        if day_expected > chrono::Duration::zero()
+            && day_sum >= day_expected
+            && self.day_target_reached != Some(today)
+        {
+            events.push(Event::DayTargetReached {
+                day_sum,
+                expected: day_expected,
+            });
+            self.day_target_reached = Some(today);
+        }
+        events
+    }
+
+    /// The closed session as it was committed. If it is not committed yet it ends now.
+    fn closed_session(
+        db: &Database,
+        start: chrono::DateTime<chrono::offset::Local>,
+        now: chrono::DateTime<chrono::offset::Local>,
+    ) -> Event {
+        let pieces: Vec<_> = db.query((start, now)).filter(|entry| entry.start >= start).collect();
+        match pieces.last() {
+            Some(last) => Event::SessionClosed {
+                start,
+                end: last.end,
+                duration: pieces.iter().fold(chrono::Duration::zero(), |sum, entry| sum + entry.duration()),
+            },
+            None => Event::SessionClosed {
+                start,
+                end: now,
+                duration: now - start,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WorktimeEntry;
+
+    #[test]
+    fn test_event_tracker() {
+        let mut tracker = EventTracker::default();
+        let start: chrono::DateTime<chrono::offset::Local> = "2023-05-03T08:00:00+02:00".parse().unwrap();
+        let end: chrono::DateTime<chrono::offset::Local> = "2023-05-03T10:00:00+02:00".parse().unwrap();
+        let now: chrono::DateTime<chrono::offset::Local> = "2023-05-03T10:10:00+02:00".parse().unwrap();
+        let expected = chrono::Duration::hours(8);
+
+        let db = Database::in_memory(vec![], vec![]);
+        assert_eq!(
+            tracker.update(&db, Some(start), chrono::Duration::zero(), expected, start),
+            vec![Event::SessionStarted { start }]
+        );
+        assert_eq!(tracker.update(&db, Some(start), chrono::Duration::hours(1), expected, start), vec![]);
+
+        let db = Database::in_memory(
+            vec![WorktimeEntry {
+                start,
+                end,
+                comments: "".into(),
+            }],
+            vec![],
+        );
+        assert_eq!(
+            tracker.update(&db, None, chrono::Duration::hours(2), expected, now),
+            vec![Event::SessionClosed {
+                start,
+                end,
+                duration: chrono::Duration::hours(2),
+            }]
+        );
+
+        let events = tracker.update(&db, None, chrono::Duration::hours(8), expected, now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name(), "day_target_reached");
+        assert_eq!(tracker.update(&db, None, chrono::Duration::hours(9), expected, now), vec![]);
+    }
+
+    #[test]
+    fn test_env_vars() {
+        let event = Event::SessionClosed {
+            start: "2023-05-03T08:00:00+02:00".parse().unwrap(),
+            end: "2023-05-03T10:00:00+02:00".parse().unwrap(),
+            duration: chrono::Duration::minutes(90),
+        };
+        let vars = event.env_vars();
+        assert!(vars.contains(&("WORKTIME_EVENT", "session_closed".to_string())));
+        assert!(vars.contains(&("WORKTIME_SESSION_DURATION", "5400".to_string())));
+    }
+
+    #[test]
+    fn test_run_all_reports_failures() {
+        let errors = run_all(&["/nonexistent/worktime-hook".to_string()], &Event::AutoSaved, false);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_hook_worker_keeps_event_order() {
+        let dir = std::env::temp_dir().join(format!("worktime-hooks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("events.log");
+        // the first event is slow, so it would finish last if the events ran concurrently
+        let hook = dir.join("hook.sh");
+        std::fs::write(
+            &hook,
+            format!(
+                "#!/bin/sh\n[ \"$WORKTIME_EVENT\" = session_started ] && sleep 0.3\necho $WORKTIME_EVENT >> {}\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&hook, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+        let hooks = vec![hook.display().to_string()];
+        let worker = HookWorker::start(false);
+        let queue = worker.queue();
+        queue.fire(
+            &hooks,
+            Event::SessionStarted {
+                start: "2023-05-03T08:00:00+02:00".parse().unwrap(),
+            },
+        );
+        queue.fire(&hooks, Event::AutoSaved);
+        drop(queue);
+        worker.stop();
+
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "session_started\nauto_saved\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
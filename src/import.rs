@@ -0,0 +1,335 @@
+use chrono::{offset::TimeZone, Local};
+use crate::database::Database;
+use crate::models::WorktimeEntry;
+
+fn local_from_naive(naive: &chrono::NaiveDateTime) -> Result<chrono::DateTime<chrono::offset::Local>, String> {
+    Local
+        .from_local_datetime(naive)
+        .earliest()
+        .ok_or(format!("{} does not exist in local time zone", naive))
+}
+
+fn join_non_empty(parts: &[&str]) -> String {
+    parts
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Splits a timewarrior tag list, respecting double quoted tags
+fn split_timewarrior_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            '"' => quoted = !quoted,
+            ' ' if !quoted => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Parses a timewarrior data file (e.g. ~/.timewarrior/data/2023-01.data).
+///
+/// Lines look like `inc 20230123T073943Z - 20230123T095218Z # tag1 "tag 2" # "annotation"`.
+/// Tags and annotation become the comment. Open intervals (still running) are skipped.
+pub fn parse_timewarrior(content: &str) -> Result<Vec<WorktimeEntry>, String> {
+    let mut entries = Vec::new();
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let Some(interval) = line.strip_prefix("inc ") else {
+            continue;
+        };
+        let mut sections = interval.splitn(3, " # ");
+        let times: Vec<&str> = sections.next().unwrap_or("").split_whitespace().collect();
+        if times.len() != 3 || times[1] != "-" {
+            // open interval
+            continue;
+        }
+        let parse = |time: &str| {
+            chrono::NaiveDateTime::parse_from_str(time, "%Y%m%dT%H%M%SZ")
+                .map(|naive| chrono::Utc.from_utc_datetime(&naive).with_timezone(&Local))
+                .map_err(|e| format!("line {}: invalid time '{}': {}", line_number + 1, time, e))
+        };
+        let start = parse(times[0])?;
+        let end = parse(times[2])?;
+        let tags = split_timewarrior_words(sections.next().unwrap_or("")).join(", ");
+        let annotation = split_timewarrior_words(sections.next().unwrap_or("")).join(" ");
+        entries.push(WorktimeEntry {
+            start,
+            end,
+            comments: join_non_empty(&[&annotation, &tags]),
+        });
+    }
+    Ok(entries)
+}
+
+fn parse_csv_date(text: &str) -> Option<chrono::NaiveDate> {
+    ["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y", "%d/%m/%Y"]
+        .iter()
+        .find_map(|format| chrono::NaiveDate::parse_from_str(text.trim(), format).ok())
+}
+
+fn parse_csv_time(text: &str) -> Option<chrono::NaiveTime> {
+    ["%H:%M:%S", "%H:%M", "%I:%M:%S %p", "%I:%M %p"]
+        .iter()
+        .find_map(|format| chrono::NaiveTime::parse_from_str(text.trim(), format).ok())
+}
+
+/// Parses a Toggl or Clockify detailed CSV export.
+///
+/// The columns are looked up by their header names ("Start date", "Start time", "End date",
+/// "End time", and optionally "Project", "Description" and "Tags"), so both layouts work.
+pub fn parse_toggl_csv<R: std::io::Read>(reader: R) -> Result<Vec<WorktimeEntry>, String> {
+    let mut rdr = csv::Reader::from_reader(reader);
+    let headers = rdr.headers().map_err(|e| e.to_string())?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let start_date = column("start date").ok_or("missing column 'Start date'")?;
+    let start_time = column("start time").ok_or("missing column 'Start time'")?;
+    let end_date = column("end date").ok_or("missing column 'End date'")?;
+    let end_time = column("end time").ok_or("missing column 'End time'")?;
+    let project = column("project");
+    let description = column("description");
+    let tags = column("tags");
+
+    let mut entries = Vec::new();
+    for (record_number, record) in rdr.records().enumerate() {
+        let record = record.map_err(|e| e.to_string())?;
+        let field = |index: usize| record.get(index).unwrap_or("");
+        let optional_field = |index: Option<usize>| index.map(field).unwrap_or("");
+        let parse = |date: usize, time: usize| -> Result<chrono::DateTime<chrono::offset::Local>, String> {
+            let naive_date = parse_csv_date(field(date))
+                .ok_or(format!("record {}: invalid date '{}'", record_number + 1, field(date)))?;
+            let naive_time = parse_csv_time(field(time))
+                .ok_or(format!("record {}: invalid time '{}'", record_number + 1, field(time)))?;
+            local_from_naive(&naive_date.and_time(naive_time))
+        };
+        entries.push(WorktimeEntry {
+            start: parse(start_date, start_time)?,
+            end: parse(end_date, end_time)?,
+            comments: join_non_empty(&[
+                optional_field(project),
+                optional_field(description),
+                optional_field(tags),
+            ]),
+        });
+    }
+    Ok(entries)
+}
+
+/// Parses a timeclock file as used by hledger and Emacs.
+///
+/// `i 2023/01/23 08:39:43 account  description` clocks in, `o 2023/01/23 10:52:18` (or `O`)
+/// clocks out. Account and description become the comment.
+pub fn parse_timeclock(content: &str) -> Result<Vec<WorktimeEntry>, String> {
+    let mut entries = Vec::new();
+    let mut clocked_in: Option<(chrono::DateTime<chrono::offset::Local>, String)> = None;
+    for (line_number, line) in content.lines().enumerate() {
+        let mut words = line.splitn(4, ' ').filter(|w| !w.is_empty());
+        let code = words.next().unwrap_or("");
+        if !matches!(code, "i" | "o" | "O") {
+            // comments, empty lines and codes we do not care about (h, b)
+            continue;
+        }
+        let date = words.next().unwrap_or("");
+        let time = words.next().unwrap_or("");
+        let text = words.next().unwrap_or("").trim();
+        let naive_date = chrono::NaiveDate::parse_from_str(date, "%Y/%m/%d")
+            .or_else(|_| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+            .map_err(|e| format!("line {}: invalid date '{}': {}", line_number + 1, date, e))?;
+        let naive_time = parse_csv_time(time)
+            .ok_or(format!("line {}: invalid time '{}'", line_number + 1, time))?;
+        let timestamp = local_from_naive(&naive_date.and_time(naive_time))?;
+
+        if code == "i" {
+            if clocked_in.is_some() {
+                return Err(format!("line {}: clock-in while already clocked in", line_number + 1));
+            }
+            // account and description are separated by two spaces (or a tab)
+            let (account, description) = text
+                .split_once("  ")
+                .or(text.split_once('\t'))
+                .unwrap_or((text, ""));
+            clocked_in = Some((timestamp, join_non_empty(&[account, description])));
+        } else {
+            let Some((start, comments)) = clocked_in.take() else {
+                return Err(format!("line {}: clock-out without clock-in", line_number + 1));
+            };
+            entries.push(WorktimeEntry {
+                start,
+                end: timestamp,
+                comments,
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// What merging imported entries into the database did (or would do in a dry-run)
+#[derive(Debug, Default)]
+pub struct MergeSummary {
+    pub added: Vec<WorktimeEntry>,
+    /// entries which already exist with identical start and end
+    pub duplicates: usize,
+    /// entries which overlap an existing entry (or an earlier imported one) and were skipped
+    pub conflicts: Vec<WorktimeEntry>,
+    /// entries which do not end after their start and were skipped
+    pub invalid: Vec<WorktimeEntry>,
+}
+
+/// Merges imported entries into the database, skipping duplicates, overlapping and invalid entries.
+/// With `dry_run` the database is left untouched.
+pub fn merge_entries(db: &mut Database, mut entries: Vec<WorktimeEntry>, dry_run: bool) -> MergeSummary {
+    entries.sort();
+    let mut summary = MergeSummary::default();
+    for entry in entries {
+        if entry.end <= entry.start {
+            summary.invalid.push(entry);
+            continue;
+        }
+        let existing: Vec<&WorktimeEntry> = db.overlapping_rows((entry.start, entry.end)).collect();
+        // added entries are sorted and do not overlap, so the last one has the latest end:
+        let overlaps_added = summary.added.last().is_some_and(|added| entry.start < added.end);
+        if existing.iter().any(|x| x.start == entry.start && x.end == entry.end) {
+            summary.duplicates += 1;
+        } else if !existing.is_empty() || overlaps_added {
+            summary.conflicts.push(entry);
+        } else {
+            summary.added.push(entry);
+        }
+    }
+    if !dry_run {
+        for entry in summary.added.iter() {
+            db.insert_worktime(entry.clone());
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(time: &str) -> chrono::DateTime<chrono::offset::Local> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_timewarrior() {
+        let content = "inc 20230123T073943Z - 20230123T095218Z # coding \"project foo\" # \"fixed bug\"\n\
+                       inc 20230123T101221Z - 20230123T111740Z\n\
+                       inc 20230123T115408Z # still running\n";
+        let entries = parse_timewarrior(content).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                WorktimeEntry {
+                    start: local("2023-01-23T07:39:43+00:00"),
+                    end: local("2023-01-23T09:52:18+00:00"),
+                    comments: "fixed bug, coding, project foo".into(),
+                },
+                WorktimeEntry {
+                    start: local("2023-01-23T10:12:21+00:00"),
+                    end: local("2023-01-23T11:17:40+00:00"),
+                    comments: "".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_toggl_csv() {
+        let content = "User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags\n\
+                       Jo,jo@example.com,,foo,,Review,No,2023-01-23,08:39:43,2023-01-23,10:52:18,02:12:35,meeting\n";
+        let entries = parse_toggl_csv(content.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].duration(), chrono::Duration::seconds(2 * 3600 + 12 * 60 + 35));
+        assert_eq!(entries[0].comments, "foo, Review, meeting");
+    }
+
+    #[test]
+    fn test_parse_clockify_csv() {
+        let content = "Project,Client,Description,Task,User,Group,Email,Tags,Billable,Start Date,Start Time,End Date,End Time\n\
+                       foo,,Review,,Jo,,jo@example.com,,No,01/23/2023,11:30:00 PM,01/24/2023,12:15:00 AM\n";
+        let entries = parse_toggl_csv(content.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].duration(), chrono::Duration::minutes(45));
+        assert_eq!(entries[0].comments, "foo, Review");
+    }
+
+    #[test]
+    fn test_parse_timeclock() {
+        let content = "; hledger timeclock\n\
+                       i 2023/01/23 08:39:43 client:foo  code review\n\
+                       o 2023/01/23 10:52:18\n\
+                       i 2023-01-23 11:12:21 internal\n\
+                       O 2023-01-23 12:17:40\n";
+        let entries = parse_timeclock(content).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].comments, "client:foo, code review");
+        assert_eq!(entries[0].duration(), chrono::Duration::seconds(2 * 3600 + 12 * 60 + 35));
+        assert_eq!(entries[1].comments, "internal");
+        assert!(parse_timeclock("o 2023/01/23 10:52:18\n").is_err());
+    }
+
+    #[test]
+    fn test_merge_entries() {
+        let existing = WorktimeEntry {
+            start: local("2023-01-23T08:00:00+01:00"),
+            end: local("2023-01-23T10:00:00+01:00"),
+            comments: "".into(),
+        };
+        let mut db = Database::in_memory(vec![existing.clone()], vec![]);
+        let new = WorktimeEntry {
+            start: local("2023-01-23T11:00:00+01:00"),
+            end: local("2023-01-23T12:00:00+01:00"),
+            comments: "imported".into(),
+        };
+        let overlapping = WorktimeEntry {
+            start: local("2023-01-23T09:00:00+01:00"),
+            end: local("2023-01-23T10:30:00+01:00"),
+            comments: "imported".into(),
+        };
+        let overlapping_new = WorktimeEntry {
+            start: local("2023-01-23T11:30:00+01:00"),
+            end: local("2023-01-23T13:00:00+01:00"),
+            comments: "imported".into(),
+        };
+        let empty = WorktimeEntry {
+            start: local("2023-01-23T14:00:00+01:00"),
+            end: local("2023-01-23T14:00:00+01:00"),
+            comments: "imported".into(),
+        };
+
+        let summary = merge_entries(
+            &mut db,
+            vec![existing.clone(), new.clone(), overlapping.clone(), overlapping_new.clone(), empty.clone()],
+            true,
+        );
+        assert_eq!(summary.added, vec![new.clone()]);
+        assert_eq!(summary.duplicates, 1);
+        assert_eq!(summary.conflicts, vec![overlapping.clone(), overlapping_new]);
+        assert_eq!(summary.invalid, vec![empty]);
+        assert_eq!(db.rows().len(), 1);
+
+        merge_entries(&mut db, vec![existing.clone(), new.clone(), overlapping], false);
+        assert_eq!(db.rows(), vec![existing, new]);
+    }
+}
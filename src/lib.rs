@@ -0,0 +1,39 @@
+//! Worktime entries and special days, their storage in csv files, queries, overtime and reports.
+//! The `worktime` binary adds activity monitoring and the command line interface on top.
+//!
+//! ```no_run
+//! let db = worktime::Database::init(
+//!     "/home/me/.worktime.csv".into(),
+//!     "/home/me/.special_days.csv".into(),
+//!     worktime::locale::Locale::En,
+//! ).unwrap();
+//! let now = chrono::Local::now();
+//! let day = worktime::report::DayReport::collect(&db, now.date_naive(), chrono::Duration::hours(40), now, None);
+//! println!("worked today: {}", worktime::utils::format_chrono_duration(&day.worked));
+//! let durations = worktime::duration::DurationFormat::default();
+//! print!("{}", worktime::report::render_day(worktime::report::Format::Markdown, &durations, worktime::locale::Locale::En, &day));
+//! ```
+
+pub mod utils;
+pub mod duration;
+pub mod locale;
+pub mod billing;
+pub mod models;
+pub mod config;
+pub mod database;
+pub mod report;
+pub mod export;
+pub mod calendar;
+pub mod import;
+pub mod timesheet;
+pub mod status;
+pub mod hooks;
+pub mod forecast;
+pub mod clock;
+pub mod sync;
+pub mod apps;
+pub mod gitlog;
+
+pub use config::Config;
+pub use database::Database;
+pub use models::{SpecialDayEntry, SpecialDayType, WorktimeEntry};
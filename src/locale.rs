@@ -0,0 +1,244 @@
+use chrono::Datelike;
+
+/// Language of user-visible texts, format of dates and the first day of the week
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Locale {
+    /// English, ISO dates (`2023-05-02`), weeks start on monday
+    En,
+    /// English, US dates (`05/02/2023`), weeks start on sunday
+    EnUs,
+    /// German, dates like `02.05.2023`, weeks start on monday
+    De,
+}
+
+/// German translations of the English texts. `{}` are placeholders, filled in order.
+const GERMAN: &[(&str, &str)] = &[
+    // reports
+    ("Current: Day: {}, Week: {}", "Aktuell: Tag: {}, Woche: {}"),
+    ("{} (billed {})", "{} (abgerechnet {})"),
+    ("| {} Start working (after {} break)", "| {} Arbeitsbeginn (nach {} Pause)"),
+    ("| {} Start working", "| {} Arbeitsbeginn"),
+    ("| {} Stopped working (after {})", "| {} Arbeitsende (nach {})"),
+    ("| {} Working... ({})", "| {} Arbeite... ({})"),
+    ("{}: {}  deviation: {}", "{}: {}  Abweichung: {}"),
+    ("{} (running)", "{} (läuft)"),
+    ("Worked: {} of {}, week: {}", "Gearbeitet: {} von {}, Woche: {}"),
+    ("Week: {} of {}, deviation: {}", "Woche: {} von {}, Abweichung: {}"),
+    ("Week from {}", "Woche ab {}"),
+    ("Start", "Beginn"),
+    ("End", "Ende"),
+    ("Duration", "Dauer"),
+    ("Comment", "Kommentar"),
+    ("Day", "Tag"),
+    ("Worked", "Gearbeitet"),
+    ("Expected", "Soll"),
+    ("Deviation", "Abweichung"),
+    ("Note", "Hinweis"),
+    ("Weekend", "Wochenende"),
+    ("Vacation", "Urlaub"),
+    ("Sick", "Krank"),
+    ("Leave", "Freistellung"),
+    ("Holiday", "Feiertag"),
+    // timesheet
+    ("Timesheet {}", "Stundenzettel {}"),
+    ("Employee:", "Mitarbeiter:"),
+    ("Contract hours:", "Vertragliche Arbeitszeit:"),
+    ("{}h per week", "{}h pro Woche"),
+    ("Breaks", "Pausen"),
+    ("Total", "Gesamt"),
+    ("Type", "Art"),
+    ("Balance: {}", "Saldo: {}"),
+    ("Date, signature employee", "Datum, Unterschrift Mitarbeiter"),
+    ("Date, signature supervisor", "Datum, Unterschrift Vorgesetzte"),
+    // status
+    ("{} day {} week {} overtime {}", "{} Tag {} Woche {} Überstunden {}"),
+    ("Today: {}\nWeek: {}\nOvertime: {}", "Heute: {}\nWoche: {}\nÜberstunden: {}"),
+    ("active", "aktiv"),
+    ("idle", "inaktiv"),
+    ("paused", "pausiert"),
+    ("stopped", "gestoppt"),
+    // monitoring
+    ("Time for a break", "Zeit für eine Pause"),
+    ("You've worked {} without a break", "{} ohne Pause gearbeitet"),
+    ("Day target reached", "Tagesziel erreicht"),
+    ("You reached today's expected {}", "Heutige Sollzeit von {} erreicht"),
+    ("Stop working", "Feierabend"),
+    ("You're at {} today", "Heute bisher {}"),
+    ("Weekly target reached", "Wochenziel erreicht"),
+    ("You worked {} this week", "Diese Woche {} gearbeitet"),
+    ("break {}", "Pause {}"),
+    ("now", "jetzt"),
+    ("no worktime recorded", "keine Arbeitszeit erfasst"),
+    ("Week: {}", "Woche: {}"),
+    ("(today)", "(heute)"),
+    ("Day: {}  Expected: {}  Overtime: {}", "Tag: {}  Soll: {}  Überstunden: {}"),
+    ("Paused", "Pausiert"),
+    ("Active ({}s since last input)", "Aktiv ({}s seit der letzten Eingabe)"),
+    ("Idle for {}m {}s", "Inaktiv seit {}m {}s"),
+    ("No activity detected yet", "Noch keine Aktivität erkannt"),
+    ("Comment for current session: {}_", "Kommentar für die laufende Sitzung: {}_"),
+    (
+        "[c] comment  [p] pause/resume  [←/→] previous/next day  [t] today  [q] quit",
+        "[c] Kommentar  [p] Pause/Weiter  [←/→] vorheriger/nächster Tag  [t] heute  [q] Beenden",
+    ),
+    ("Comment set", "Kommentar gesetzt"),
+    ("No session running", "Keine laufende Sitzung"),
+    ("Resumed", "Fortgesetzt"),
+    ("Paused, no worktime is recorded until resumed", "Pausiert, bis zum Fortsetzen wird keine Arbeitszeit erfasst"),
+    // command line
+    ("Using data file {}", "Datendatei {}"),
+    ("Using special_day file {}", "Datei der besonderen Tage {}"),
+    (
+        "Note: Database could not be fully initialized. \
+    Continuing with partially initialized database. Could not read {}: {}",
+        "Hinweis: Die Datenbank konnte nicht vollständig geladen werden. \
+    Es wird mit den geladenen Daten fortgefahren. {} konnte nicht gelesen werden: {}",
+    ),
+    ("Import of {} failed: {}", "Import von {} fehlgeschlagen: {}"),
+    ("Add {} -> {} ({}) \"{}\"?", "{} -> {} ({}) \"{}\" hinzufügen?"),
+    ("Added {} worktime entries", "{} Einträge hinzugefügt"),
+    ("Add {} -> {} ({}) \"{}\"", "Hinzufügen: {} -> {} ({}) \"{}\""),
+    ("Would add {} -> {} ({}) \"{}\"", "Würde hinzufügen: {} -> {} ({}) \"{}\""),
+    ("Skip {} -> {} (overlaps an existing entry)", "Überspringe {} -> {} (überschneidet einen vorhandenen Eintrag)"),
+    ("Skip {} -> {} (does not end after its start)", "Überspringe {} -> {} (endet nicht nach dem Beginn)"),
+    (
+        "{} new entries ({}), {} duplicates, {} overlapping and {} invalid entries skipped",
+        "{} neue Einträge ({}), {} Duplikate, {} überschneidende und {} ungültige Einträge übersprungen",
+    ),
+    ("Today: {} worked, {} breaks, {} expected", "Heute: {} gearbeitet, {} Pausen, {} Soll"),
+    ("Day balanced at {}", "Tag ausgeglichen um {}"),
+    ("Day balanced already", "Tag bereits ausgeglichen"),
+    ("Week balanced at {}", "Woche ausgeglichen um {}"),
+    ("Week balanced already", "Woche bereits ausgeglichen"),
+    (
+        "Another process is already monitoring worktime. exiting...",
+        "Ein anderer Prozess erfasst bereits die Arbeitszeit. Beende...",
+    ),
+    ("Wrote {}", "{} geschrieben"),
+    ("Enable it with: {}", "Aktivieren mit: {}"),
+    ("Add {} -> {} ({}) from {}", "Hinzufügen: {} -> {} ({}) von {}"),
+    ("Would add {} -> {} ({}) from {}", "Würde hinzufügen: {} -> {} ({}) von {}"),
+    ("{} new entries", "{} neue Einträge"),
+    ("Split {} -> {} into {} entries", "Aufteilen: {} -> {} in {} Einträge"),
+    ("Would split {} -> {} into {} entries", "Würde aufteilen: {} -> {} in {} Einträge"),
+    ("{} entries commented", "{} Einträge kommentiert"),
+    ("Comment with \"{}\"?", "Mit \"{}\" kommentieren?"),
+    ("Outside of worktime:", "Außerhalb der Arbeitszeit:"),
+    ("overtime: {}", "Überstunden: {}"),
+    (
+        "Another process is monitoring worktime. Stop it before modifying the data file. exiting...",
+        "Ein anderer Prozess erfasst die Arbeitszeit. Vor dem Ändern der Datendatei beenden. Beende...",
+    ),
+];
+
+const WEEKDAYS_EN: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const WEEKDAYS_DE: [&str; 7] = ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"];
+const MONTHS_EN: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November",
+    "December",
+];
+const MONTHS_DE: [&str; 12] = [
+    "Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November",
+    "Dezember",
+];
+
+impl Locale {
+    /// The translation of an English text. Texts without translation are returned as they are.
+    pub fn tr<'a>(self: Self, text: &'a str) -> &'a str {
+        match self {
+            Locale::En | Locale::EnUs => text,
+            Locale::De => GERMAN.iter().find(|(en, _)| *en == text).map_or(text, |(_, de)| de),
+        }
+    }
+
+    /// `text` translated, with each `{}` replaced by the next of `args`
+    pub fn format(self: Self, text: &str, args: &[&dyn std::fmt::Display]) -> String {
+        let mut parts = self.tr(text).split("{}");
+        let mut result = parts.next().unwrap_or_default().to_string();
+        for (i, part) in parts.enumerate() {
+            if let Some(arg) = args.get(i) {
+                result.push_str(&arg.to_string());
+            }
+            result.push_str(part);
+        }
+        result
+    }
+
+    pub fn first_weekday(self: Self) -> chrono::Weekday {
+        match self {
+            Locale::En | Locale::De => chrono::Weekday::Mon,
+            Locale::EnUs => chrono::Weekday::Sun,
+        }
+    }
+
+    /// Short name, e.g. `Tue` or `Di`
+    pub fn weekday(self: Self, weekday: chrono::Weekday) -> &'static str {
+        let names = match self {
+            Locale::En | Locale::EnUs => &WEEKDAYS_EN,
+            Locale::De => &WEEKDAYS_DE,
+        };
+        names[weekday.num_days_from_monday() as usize]
+    }
+
+    /// e.g. `2023-05-02`, `05/02/2023` or `02.05.2023`
+    pub fn date(self: Self, day: chrono::NaiveDate) -> String {
+        let format = match self {
+            Locale::En => "%Y-%m-%d",
+            Locale::EnUs => "%m/%d/%Y",
+            Locale::De => "%d.%m.%Y",
+        };
+        day.format(format).to_string()
+    }
+
+    /// Weekday and date, e.g. `Tue 2023-05-02`
+    pub fn day(self: Self, day: chrono::NaiveDate) -> String {
+        format!("{} {}", self.weekday(day.weekday()), self.date(day))
+    }
+
+    /// Weekday, date and time, e.g. `Tue 2023-05-02 08:00:00`
+    pub fn day_time(self: Self, time: chrono::DateTime<chrono::offset::Local>) -> String {
+        format!("{} {}", self.day(time.date_naive()), time.format("%T"))
+    }
+
+    /// Month and year, e.g. `May 2023` or `Mai 2023`
+    pub fn month(self: Self, day: chrono::NaiveDate) -> String {
+        let names = match self {
+            Locale::En | Locale::EnUs => &MONTHS_EN,
+            Locale::De => &MONTHS_DE,
+        };
+        format!("{} {}", names[day.month0() as usize], day.year())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translations_keep_placeholders() {
+        for (en, de) in GERMAN {
+            assert_eq!(en.matches("{}").count(), de.matches("{}").count(), "{}", en);
+            assert_eq!(GERMAN.iter().filter(|(other, _)| other == en).count(), 1, "{}", en);
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Locale::En.format("Week from {}", &[&"Mon"]), "Week from Mon");
+        assert_eq!(Locale::De.format("Worked: {} of {}, week: {}", &[&1, &2, &3]), "Gearbeitet: 1 von 2, Woche: 3");
+        assert_eq!(Locale::De.format("not translated {}", &[&1]), "not translated 1");
+        assert_eq!(Locale::De.tr("Weekend"), "Wochenende");
+    }
+
+    #[test]
+    fn test_dates() {
+        let day: chrono::NaiveDate = "2023-05-02".parse().unwrap();
+        assert_eq!(Locale::En.day(day), "Tue 2023-05-02");
+        assert_eq!(Locale::EnUs.day(day), "Tue 05/02/2023");
+        assert_eq!(Locale::De.day(day), "Di 02.05.2023");
+        assert_eq!(Locale::De.month(day), "Mai 2023");
+        assert_eq!(Locale::En.month(day), "May 2023");
+        assert_eq!(Locale::EnUs.first_weekday(), chrono::Weekday::Sun);
+    }
+}
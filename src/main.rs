@@ -1,87 +1,515 @@
-mod utils;
-mod models;
-mod config;
-mod database;
 mod idle_detection;
 mod monitoring;
 mod cli;
+mod tui;
+mod notifications;
+mod daemon;
+
+// the library modules, so `crate::database` etc. resolve in the binary's modules as well
+use worktime::{apps, billing, calendar, clock, config, database, duration, export, forecast, gitlog, hooks, import, locale, models, report, status, sync, timesheet, utils};
 
 use std::sync::*;
-use chrono::Datelike;
 use clap::Parser;
 
-use crate::cli::Args;
+use crate::cli::{Args, BarFormat, Command, DurationRounding, DurationStyle, ExportFormat, ImportFormat, ReportFormat};
 use crate::config::Config;
 use crate::database::Database;
-use crate::models::SpecialDayType;
-use crate::monitoring::run_interactive_monitoring;
-use crate::utils::format_chrono_duration;
+use crate::monitoring::{run_daemon_monitoring, run_interactive_monitoring};
+use crate::utils::{ask_yes_no, start_of_day};
 
 fn main() {
     let args = Args::parse();
-    let cfg: Config = confy::load("worktime", None).unwrap();
+    let mut cfg: Config = confy::load("worktime", None).unwrap();
+    if let Some(style) = args.duration_style {
+        cfg.duration_style = match style {
+            DurationStyle::Hms => duration::DurationStyle::Hms,
+            DurationStyle::Clock => duration::DurationStyle::Clock,
+            DurationStyle::Decimal => duration::DurationStyle::Decimal,
+            DurationStyle::Industrial => duration::DurationStyle::Industrial,
+        };
+    }
+    if let Some(rounding) = args.duration_rounding {
+        cfg.duration_rounding = match rounding {
+            DurationRounding::Down => duration::Rounding::Down,
+            DurationRounding::Nearest => duration::Rounding::Nearest,
+            DurationRounding::Up => duration::Rounding::Up,
+        };
+    }
+
+    let durations = cfg.duration_format();
+    let locale = cfg.locale;
 
     let data_path = expanduser::expanduser(cfg.data_file.as_str()).unwrap();
-    println!("Using data file {}", data_path.display());
+    eprintln!("{}", locale.format("Using data file {}", &[&data_path.display()]));
 
     let special_day_path = expanduser::expanduser(cfg.special_day_file.as_str()).unwrap();
-    println!("Using special_day file {}", special_day_path.display());
+    eprintln!("{}", locale.format("Using special_day file {}", &[&special_day_path.display()]));
 
-    let database = Arc::new(Mutex::new(
-        Database::init(data_path, special_day_path).unwrap(),
-    ));
-    if args.overtime {
+    let mut database = Database::init(data_path, special_day_path, locale).unwrap();
+    database.set_day_boundary(cfg.storage_day_boundary());
+    database.set_first_weekday(locale.first_weekday());
+    let database = Arc::new(Mutex::new(database));
+    if let Some(command) = args.command {
+        match command {
+            Command::Export { format, from, to, out, raw } => {
+                let range = (start_of_day(from), start_of_day(to.succ_opt().unwrap()));
+                let mut writer: Box<dyn std::io::Write> = match out {
+                    Some(path) => Box::new(std::fs::File::create(path).unwrap()),
+                    None => Box::new(std::io::stdout()),
+                };
+                let db = database.lock().unwrap();
+                let ics = matches!(format, ExportFormat::Ics);
+                let entries = export::export_entries(&db, range, ics, cfg.billing().filter(|_| !raw));
+                match format {
+                    ExportFormat::Ics => {
+                        export::write_ics(&mut writer, entries.into_iter(), db.query_special_days(range))
+                            .unwrap();
+                    }
+                    ExportFormat::Timeclock => {
+                        export::write_timeclock(&mut writer, entries.into_iter()).unwrap();
+                    }
+                    ExportFormat::Timew => {
+                        export::write_timewarrior(&mut writer, entries.into_iter()).unwrap();
+                    }
+                }
+            }
+            Command::ImportCalendar { file, yes } => {
+                let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+                let events = calendar::read_ics_file(&file, now).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+                let monitoring_lock = named_lock::NamedLock::create("worktime_monitoring").unwrap();
+                let _guard = lock_for_writing(&monitoring_lock, locale);
+                let mut db = database.lock().unwrap();
+                let mut added = 0;
+                // meetings which did not happen yet cannot fill a break:
+                for event in events.iter().filter(|e| e.start < now) {
+                    let event = calendar::CalendarEvent {
+                        end: std::cmp::min(event.end, now),
+                        ..event.clone()
+                    };
+                    for entry in calendar::uncovered_parts(&db, &[], &event) {
+                        let question = locale.format(
+                            "Add {} -> {} ({}) \"{}\"?",
+                            &[
+                                &locale.day_time(entry.start),
+                                &entry.end.format("%T"),
+                                &durations.format(&entry.duration()),
+                                &entry.comments,
+                            ],
+                        );
+                        if yes || ask_yes_no(&question) {
+                            db.insert_worktime(entry);
+                            added += 1;
+                        }
+                    }
+                }
+                if added > 0 {
+                    db.store_file().unwrap();
+                }
+                println!("{}", locale.format("Added {} worktime entries", &[&added]));
+            }
+            Command::Import { format, files, dry_run } => {
+                let mut entries = Vec::new();
+                for file in files {
+                    let parsed = match format {
+                        ImportFormat::Timew => std::fs::read_to_string(&file)
+                            .map_err(|e| e.to_string())
+                            .and_then(|content| import::parse_timewarrior(&content)),
+                        ImportFormat::Toggl => std::fs::File::open(&file)
+                            .map_err(|e| e.to_string())
+                            .and_then(import::parse_toggl_csv),
+                        ImportFormat::Timeclock => std::fs::read_to_string(&file)
+                            .map_err(|e| e.to_string())
+                            .and_then(|content| import::parse_timeclock(&content)),
+                    };
+                    match parsed {
+                        Ok(mut parsed) => entries.append(&mut parsed),
+                        Err(err) => {
+                            eprintln!("{}", locale.format("Import of {} failed: {}", &[&file.display(), &err]));
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                let monitoring_lock = named_lock::NamedLock::create("worktime_monitoring").unwrap();
+                let _guard = lock_for_writing(&monitoring_lock, locale);
+                let mut db = database.lock().unwrap();
+                let summary = import::merge_entries(&mut db, entries, dry_run);
+                for entry in summary.added.iter() {
+                    let text = if dry_run { "Would add {} -> {} ({}) \"{}\"" } else { "Add {} -> {} ({}) \"{}\"" };
+                    let args: [&dyn std::fmt::Display; 4] = [
+                        &locale.day_time(entry.start),
+                        &locale.day_time(entry.end),
+                        &durations.format(&entry.duration()),
+                        &entry.comments,
+                    ];
+                    println!("{}", locale.format(text, &args));
+                }
+                for entry in summary.conflicts.iter() {
+                    println!(
+                        "{}",
+                        locale.format(
+                            "Skip {} -> {} (overlaps an existing entry)",
+                            &[&locale.day_time(entry.start), &locale.day_time(entry.end)],
+                        )
+                    );
+                }
+                for entry in summary.invalid.iter() {
+                    println!(
+                        "{}",
+                        locale.format(
+                            "Skip {} -> {} (does not end after its start)",
+                            &[&locale.day_time(entry.start), &locale.day_time(entry.end)],
+                        )
+                    );
+                }
+                let added_duration = summary
+                    .added
+                    .iter()
+                    .fold(chrono::Duration::zero(), |sum, entry| sum + entry.duration());
+                println!(
+                    "{}",
+                    locale.format(
+                        "{} new entries ({}), {} duplicates, {} overlapping and {} invalid entries skipped",
+                        &[
+                            &summary.added.len(),
+                            &durations.format(&added_duration),
+                            &summary.duplicates,
+                            &summary.conflicts.len(),
+                            &summary.invalid.len(),
+                        ],
+                    )
+                );
+                if !dry_run && !summary.added.is_empty() {
+                    db.store_file().unwrap();
+                }
+            }
+            Command::Timesheet { month, out } => {
+                let mut writer: Box<dyn std::io::Write> = match out {
+                    Some(path) => Box::new(std::fs::File::create(path).unwrap()),
+                    None => Box::new(std::io::stdout()),
+                };
+                let db = database.lock().unwrap();
+                let days = timesheet::collect_month(&db, month, chrono::Duration::hours(cfg.weekly_hours));
+                timesheet::write_html(&mut writer, &cfg.employee_name, cfg.weekly_hours, &days, &durations, locale)
+                    .unwrap();
+            }
+            Command::Forecast { with_overtime } => {
+                let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+                let live_state_path = expanduser::expanduser(cfg.live_state_file.as_str()).unwrap();
+                let session_start = status::LiveState::load(&live_state_path)
+                    .ok()
+                    .filter(|state| state.is_fresh(now))
+                    .and_then(|state| state.session_start);
+                let db = database.lock().unwrap();
+                let weekly_worktime = chrono::Duration::hours(cfg.weekly_hours);
+                let facts = forecast::WorkFacts::collect(&db, session_start, weekly_worktime, now);
+                let (day_balance, week_balance) = if with_overtime {
+                    let (start_of_week, _) = db.get_week_bounds(now);
+                    (report::overtime(&db, &cfg, now), report::overtime(&db, &cfg, start_of_week))
+                } else {
+                    (chrono::Duration::zero(), chrono::Duration::zero())
+                };
+
+                println!(
+                    "{}",
+                    locale.format(
+                        "Today: {} worked, {} breaks, {} expected",
+                        &[
+                            &durations.format(&facts.worked_today),
+                            &durations.format(&facts.breaks_today),
+                            &durations.format(&facts.expected_today),
+                        ],
+                    )
+                );
+                let remaining_day = facts.expected_today - facts.worked_today - day_balance;
+                match forecast::balanced_at(&facts, &cfg.break_rules, remaining_day) {
+                    Some(time) => println!(
+                        "{}",
+                        locale.format("Day balanced at {}", &[&forecast::format_clock_time(time, now, locale)])
+                    ),
+                    None => println!("{}", locale.tr("Day balanced already")),
+                }
+                let remaining_week = facts.expected_week - facts.worked_week - week_balance;
+                match forecast::week_balanced_at(&facts, &cfg.break_rules, remaining_week) {
+                    Some(time) => println!(
+                        "{}",
+                        locale.format("Week balanced at {}", &[&forecast::format_clock_time(time, now, locale)])
+                    ),
+                    None => println!("{}", locale.tr("Week balanced already")),
+                }
+            }
+            Command::Daemon { log_file } => {
+                if let Some(log_file) = log_file {
+                    daemon::redirect_output(&log_file).unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    });
+                }
+                let monitoring_lock = named_lock::NamedLock::create("worktime_monitoring").unwrap();
+                let Ok(_guard) = monitoring_lock.try_lock() else {
+                    eprintln!("{}", locale.tr("Another process is already monitoring worktime. exiting..."));
+                    std::process::exit(1);
+                };
+                run_daemon_monitoring(database, &cfg);
+            }
+            Command::InstallService { log_file } => {
+                let executable = std::env::current_exe().unwrap();
+                let installed = daemon::user_unit_dir(std::env::var_os("XDG_CONFIG_HOME"))
+                    .and_then(|unit_dir| daemon::install_service(&unit_dir, &executable, log_file.as_deref()));
+                match installed {
+                    Ok(path) => {
+                        println!("{}", locale.format("Wrote {}", &[&path.display()]));
+                        println!(
+                            "{}",
+                            locale.format(
+                                "Enable it with: {}",
+                                &[&"systemctl --user daemon-reload && systemctl --user enable --now worktime"],
+                            )
+                        );
+                    }
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Command::Merge { files, host, dry_run } => {
+                let monitoring_lock = named_lock::NamedLock::create("worktime_monitoring").unwrap();
+                let _guard = lock_for_writing(&monitoring_lock, locale);
+                let host_file = expanduser::expanduser(cfg.host_file.as_str()).unwrap();
+                let mut host_entries = sync::read_host_entries(&host_file).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+                let mut db = database.lock().unwrap();
+                let mut added_count = 0;
+                for file in files {
+                    let entries = sync::read_data_file(&file).unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    });
+                    let file_host = host
+                        .clone()
+                        .unwrap_or_else(|| file.file_stem().unwrap_or_default().to_string_lossy().to_string());
+                    let added = if dry_run {
+                        sync::plan_merge(&db, &[], entries)
+                    } else {
+                        sync::merge_host(&mut db, &mut host_entries, &file_host, entries, &[])
+                    };
+                    for entry in added.iter() {
+                        let text = if dry_run { "Would add {} -> {} ({}) from {}" } else { "Add {} -> {} ({}) from {}" };
+                        let args: [&dyn std::fmt::Display; 4] = [
+                            &locale.day_time(entry.start),
+                            &locale.day_time(entry.end),
+                            &durations.format(&entry.duration()),
+                            &file_host,
+                        ];
+                        println!("{}", locale.format(text, &args));
+                    }
+                    added_count += added.len();
+                }
+                println!("{}", locale.format("{} new entries", &[&added_count]));
+                if !dry_run && added_count > 0 {
+                    db.store_file().unwrap();
+                    sync::write_host_entries(&host_file, &host_entries).unwrap();
+                }
+            }
+            Command::SplitAtDayBoundary { dry_run } => {
+                let monitoring_lock = named_lock::NamedLock::create("worktime_monitoring").unwrap();
+                let _guard = lock_for_writing(&monitoring_lock, locale);
+                let mut db = database.lock().unwrap();
+                for row in db.rows().iter() {
+                    let pieces = row.clone().split_at_day_boundary(cfg.day_boundary);
+                    if pieces.len() > 1 {
+                        let text =
+                            if dry_run { "Would split {} -> {} into {} entries" } else { "Split {} -> {} into {} entries" };
+                        let args: [&dyn std::fmt::Display; 3] =
+                            [&locale.day_time(row.start), &locale.day_time(row.end), &pieces.len()];
+                        println!("{}", locale.format(text, &args));
+                    }
+                }
+                if !dry_run && db.split_rows_at_day_boundary(cfg.day_boundary) > 0 {
+                    db.store_file().unwrap();
+                }
+            }
+            Command::Apps { day, tag } => {
+                let monitoring_lock = named_lock::NamedLock::create("worktime_monitoring").unwrap();
+                let _guard = if tag { Some(lock_for_writing(&monitoring_lock, locale)) } else { None };
+                let app_file = expanduser::expanduser(cfg.app_file.as_str()).unwrap();
+                let usages = apps::read_app_usages(&app_file).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+                let rules = apps::Rules::compile(&cfg.window_rules).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                });
+                let day = day.unwrap_or_else(|| chrono::Local::now().date_naive());
+                let range = (start_of_day(day), start_of_day(day + chrono::Duration::days(1)));
+                let mut db = database.lock().unwrap();
+                let rows: Vec<models::WorktimeEntry> = db.overlapping_rows(range).cloned().collect();
+                let mut tagged = 0;
+                for mut row in rows {
+                    let entry_range = (row.start, row.end);
+                    if tag && row.comments.is_empty() {
+                        if let Some(comment) = rules.comment_for_range(&usages, entry_range) {
+                            if db.set_comment(&row, comment.clone()) {
+                                row.comments = comment;
+                                tagged = tagged + 1;
+                            }
+                        }
+                    }
+                    println!(
+                        "{} -> {} ({}) {}",
+                        row.start.format("%H:%M"),
+                        row.end.format("%H:%M"),
+                        durations.format(&row.duration()),
+                        row.comments
+                    );
+                    for (class, duration) in apps::breakdown(&usages, entry_range) {
+                        println!("    {:>8} {}", durations.format(&duration), class);
+                    }
+                }
+                if tag {
+                    println!("{}", locale.format("{} entries commented", &[&tagged]));
+                    if tagged > 0 {
+                        db.store_file().unwrap();
+                    }
+                }
+            }
+            Command::GitLog { repos, from, to, all_authors, suggest, yes } => {
+                let monitoring_lock = named_lock::NamedLock::create("worktime_monitoring").unwrap();
+                let _guard = if suggest { Some(lock_for_writing(&monitoring_lock, locale)) } else { None };
+                let to = to.unwrap_or_else(|| chrono::Local::now().date_naive());
+                let from = from.unwrap_or(to - chrono::Duration::days(6));
+                let range = (start_of_day(from), start_of_day(to.succ_opt().unwrap()));
+                let mut commits = Vec::new();
+                for repo in repos {
+                    let author = if all_authors { None } else { gitlog::configured_author(&repo) };
+                    let repo_commits = gitlog::read_commits(&repo, range, author.as_deref()).unwrap_or_else(|e| {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    });
+                    commits.extend(repo_commits);
+                }
+                let mut db = database.lock().unwrap();
+                let rows: Vec<models::WorktimeEntry> = db.overlapping_rows(range).cloned().collect();
+                let (entries, outside) = gitlog::attach(&rows, &commits);
+                let mut commented = 0;
+                for (entry, entry_commits) in entries.iter() {
+                    println!(
+                        "{} {} -> {} ({}) {}",
+                        locale.day(entry.start.date_naive()),
+                        entry.start.format("%H:%M"),
+                        entry.end.format("%H:%M"),
+                        durations.format(&entry.duration()),
+                        entry.comments
+                    );
+                    for commit in entry_commits {
+                        println!("    {} {} {}: {}", commit.time.format("%H:%M"), commit.short_hash(), commit.repo, commit.subject);
+                    }
+                    if !suggest || !entry.comments.is_empty() {
+                        continue;
+                    }
+                    if let Some(comment) = gitlog::suggest_comment(entry_commits) {
+                        if yes || ask_yes_no(&locale.format("Comment with \"{}\"?", &[&comment])) {
+                            if db.set_comment(entry, comment) {
+                                commented = commented + 1;
+                            }
+                        }
+                    }
+                }
+                if !outside.is_empty() {
+                    println!("{}", locale.tr("Outside of worktime:"));
+                    for commit in outside.iter() {
+                        println!(
+                            "    {} {} {} {}: {}",
+                            locale.day(commit.time.date_naive()),
+                            commit.time.format("%H:%M"),
+                            commit.short_hash(),
+                            commit.repo,
+                            commit.subject
+                        );
+                    }
+                }
+                if suggest {
+                    println!("{}", locale.format("{} entries commented", &[&commented]));
+                    if commented > 0 {
+                        db.store_file().unwrap();
+                    }
+                }
+            }
+            Command::Report { day, week, format, out } => {
+                let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+                let live_state_path = expanduser::expanduser(cfg.live_state_file.as_str()).unwrap();
+                let session_start = status::LiveState::load(&live_state_path)
+                    .ok()
+                    .filter(|state| state.is_fresh(now))
+                    .and_then(|state| state.session_start);
+                let format = match format {
+                    // colors only make sense on the terminal
+                    ReportFormat::Auto if out.is_some() => report::Format::Plain,
+                    ReportFormat::Auto => report::Format::detect(),
+                    ReportFormat::Plain => report::Format::Plain,
+                    ReportFormat::Color => report::Format::Color,
+                    ReportFormat::Json => report::Format::Json,
+                    ReportFormat::Markdown => report::Format::Markdown,
+                    ReportFormat::Html => report::Format::Html,
+                };
+                let day = day.unwrap_or(now.date_naive());
+                let weekly_worktime = chrono::Duration::hours(cfg.weekly_hours);
+                let db = database.lock().unwrap();
+                let text = if week {
+                    let mut week_report = report::WeekReport::collect(&db, day, weekly_worktime, now, session_start);
+                    if let Some(billing) = cfg.billing() {
+                        week_report.bill(&billing);
+                    }
+                    report::render_week(format, &durations, locale, &week_report)
+                } else {
+                    let mut day_report = report::DayReport::collect(&db, day, weekly_worktime, now, session_start);
+                    if let Some(billing) = cfg.billing() {
+                        day_report.bill(&billing);
+                    }
+                    report::render_day(format, &durations, locale, &day_report)
+                };
+                match out {
+                    Some(path) => std::fs::write(&path, text).unwrap_or_else(|e| {
+                        eprintln!("{}: {}", path.display(), e);
+                        std::process::exit(1);
+                    }),
+                    None => print!("{}", text),
+                }
+            }
+            Command::Status { bar } => {
+                let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+                let live_state_path = expanduser::expanduser(cfg.live_state_file.as_str()).unwrap();
+                let live_state = status::LiveState::load(&live_state_path).ok();
+                let db = database.lock().unwrap();
+                let overtime = report::overtime(&db, &cfg, now);
+                let status = status::collect_status(&db, live_state, overtime, now);
+                match bar {
+                    BarFormat::Waybar => println!("{}", status::format_waybar(&status, &durations, locale)),
+                    BarFormat::I3blocks => println!("{}", status::format_i3blocks(&status, &durations, locale)),
+                    BarFormat::Plain => println!("{}", status::format_plain(&status, &durations, locale)),
+                }
+            }
+        }
+    } else if args.overtime {
         let overtime_end: chrono::DateTime<chrono::offset::Local> =
             std::time::SystemTime::now().into();
-        let overtime = database.lock().unwrap().calculate_overtime(
-            chrono::Duration::hours(cfg.weekly_hours),
-            (cfg.cutoff_datetime, overtime_end),
-        ) + chrono::Duration::seconds((cfg.cutoff_day_overtime_hours * 3600.0) as i64);
-        println!("overtime: {}", format_chrono_duration(&overtime));
+        let overtime = report::overtime(&database.lock().unwrap(), &cfg, overtime_end);
+        println!("{}", locale.format("overtime: {}", &[&durations.format(&overtime)]));
     } else if let Some(days) = args.daysums {
-        let daysums = database.lock().unwrap().get_day_sums(days);
-        // Use floating-point division for accurate expected_per_day
-        let expected_per_day_secs = (cfg.weekly_hours as f64 / 5.0) * 3600.0;
-        let expected_per_day = chrono::Duration::seconds(expected_per_day_secs.round() as i64);
+        let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
         let db = database.lock().unwrap();
-        for (time, sum) in daysums {
-            let weekday = time.weekday();
-            // Check for special day (Vacation, Sick, Holiday)
-            let special = db.special_days.iter().find(|sd| {
-                sd.day == time.date_naive() && matches!(sd.day_type, SpecialDayType::Vacation | SpecialDayType::Sick | SpecialDayType::Holiday)
-            });
-            let expected = match (weekday, special) {
-                (chrono::Weekday::Sat | chrono::Weekday::Sun, _) => chrono::Duration::zero(),
-                (_, Some(_)) => chrono::Duration::zero(),
-                _ => expected_per_day,
-            };
-            let deviation = sum - expected;
-            let deviation_secs = deviation.num_seconds();
-            let color = if deviation_secs > 0 {
-                "\x1b[32m" // green
-            } else if deviation_secs < -3*60*60 {
-                "\x1b[31m" // red
-            } else if deviation_secs < -1*60*60 {
-                "\x1b[38;5;208m" // orange
-            } else {
-                "\x1b[33m" // yellow
-            };
-            let mut reason = String::new();
-            if expected == chrono::Duration::zero() {
-                if let Some(s) = special {
-                    reason = format!(" ({:?})", s.day_type);
-                } else if matches!(weekday, chrono::Weekday::Sat | chrono::Weekday::Sun) {
-                    reason = " (Weekend)".to_string();
-                }
+        for mut day_report in report::DayReport::collect_last(&db, days, chrono::Duration::hours(cfg.weekly_hours), now) {
+            if let Some(billing) = cfg.billing() {
+                day_report.bill(&billing);
             }
-            println!(
-                "{}: {}  deviation: {}{}\x1b[0m{}",
-                time.format("%a %Y-%m-%d"),
-                format_chrono_duration(&sum),
-                color,
-                format_chrono_duration(&deviation),
-                reason
-            );
+            let color = report::Format::detect() == report::Format::Color;
+            println!("{}", report::format_day_line(&day_report, &durations, locale, color));
         }
     } else {
         // No two processes are allowed to monitor worktime at the same time.
@@ -91,8 +519,20 @@ fn main() {
             if let Ok(_guard) = monitoring_lock.try_lock() {
                 run_interactive_monitoring(database, &cfg);
             } else {
-                println!("Another process is already monitoring worktime. exiting...")
+                println!("{}", locale.tr("Another process is already monitoring worktime. exiting..."))
             }
         };
     }
 }
+
+/// Commands modifying the data file must not run while another process is monitoring,
+/// as the monitoring process would overwrite the changes with its next save.
+fn lock_for_writing(monitoring_lock: &named_lock::NamedLock, locale: locale::Locale) -> named_lock::NamedLockGuard<'_> {
+    monitoring_lock.try_lock().unwrap_or_else(|_| {
+        println!(
+            "{}",
+            locale.tr("Another process is monitoring worktime. Stop it before modifying the data file. exiting...")
+        );
+        std::process::exit(1);
+    })
+}
@@ -24,4 +24,29 @@ impl WorktimeEntry {
     pub fn duration(self: &Self) -> chrono::Duration {
         return self.end - self.start;
     }
+
+    /// Splits the entry wherever the local time passes `boundary` (e.g. midnight). The pieces
+    /// are linked: each one ends where the next starts and all keep the comment.
+    pub fn split_at_day_boundary(self: Self, boundary: chrono::NaiveTime) -> Vec<WorktimeEntry> {
+        let mut pieces = Vec::new();
+        let mut start = self.start;
+        // the boundary of the day before might still lie after the start, e.g. for a boundary
+        // at 04:00 and a start at 01:00
+        let mut day = self.start.date_naive().pred_opt().unwrap();
+        while start < self.end {
+            let split = crate::utils::local_time_on(day, boundary);
+            day = day.succ_opt().unwrap();
+            if split <= start {
+                continue;
+            }
+            let end = std::cmp::min(split, self.end);
+            pieces.push(WorktimeEntry {
+                start,
+                end,
+                comments: self.comments.clone(),
+            });
+            start = end;
+        }
+        pieces
+    }
 }
\ No newline at end of file
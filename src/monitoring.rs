@@ -1,113 +1,500 @@
+use std::io::IsTerminal;
 use std::sync::*;
 use std::thread;
-use signal_hook::{consts::SIGINT, iterator::Signals};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGQUIT, SIGTERM};
+use signal_hook::iterator::Signals;
+use crate::apps::{self, AppRecorder, WindowTracker};
+use crate::calendar;
+use crate::report;
+use crate::clock::ClockJumpDetector;
+use crate::daemon;
 use crate::config::Config;
+use crate::hooks::{self, EventTracker, HookWorker};
 use crate::database::Database;
+use crate::notifications::{NotificationWorker, Notifier, WorkState};
+use crate::status::LiveState;
+use crate::sync;
+use crate::tui;
 use crate::idle_detection::{ActivityRecorder, create_idle_detector};
-use crate::utils::format_chrono_duration;
+use crate::utils::start_of_day;
+
+/// Set when monitoring should end. Threads waiting on it are woken up immediately.
+#[derive(Default)]
+struct Shutdown {
+    requested: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl Shutdown {
+    fn request(self: &Self) {
+        *self.requested.lock().unwrap() = true;
+        self.condvar.notify_all();
+    }
+
+    fn is_requested(self: &Self) -> bool {
+        *self.requested.lock().unwrap()
+    }
+
+    /// Sleeps for `duration` or until shutdown is requested. Returns true if it was requested.
+    fn wait(self: &Self, duration: std::time::Duration) -> bool {
+        let requested = self.requested.lock().unwrap();
+        let (requested, _) = self
+            .condvar
+            .wait_timeout_while(requested, duration, |requested| !*requested)
+            .unwrap();
+        *requested
+    }
+}
+
+/// Reloads the config file. Keeps the old config if the file cannot be read.
+/// Returns true if `timeout_minutes` changed.
+fn reload_config(cfg: &RwLock<Config>, report: bool) -> bool {
+    match confy::load::<Config>("worktime", None) {
+        Ok(new_cfg) => {
+            if report {
+                println!("Reloaded config");
+            }
+            let timeout_changed = new_cfg.timeout_minutes != cfg.read().unwrap().timeout_minutes;
+            *cfg.write().unwrap() = new_cfg;
+            timeout_changed
+        }
+        Err(err) => {
+            if report {
+                eprintln!("Failed to reload config, keeping the old one: {}", err);
+            }
+            false
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    /// full-screen terminal UI
+    Tui,
+    /// timeline printed periodically, e.g. when stdout is redirected
+    Plain,
+    /// no interactive output, only messages for the log
+    Daemon,
+}
 
 pub fn run_interactive_monitoring(database: Arc<Mutex<Database>>, cfg: &Config) {
+    let output = if std::io::stdout().is_terminal() { Output::Tui } else { Output::Plain };
+    run_monitoring(database, cfg, output);
+}
+
+/// Monitoring without interactive output, e.g. as systemd service. Notifies systemd about
+/// readiness and sends watchdog pings, if requested by the service manager.
+pub fn run_daemon_monitoring(database: Arc<Mutex<Database>>, cfg: &Config) {
+    run_monitoring(database, cfg, Output::Daemon);
+}
+
+/// A live state file left behind means the previous monitoring process did not end cleanly.
+/// Its session is closed at the last input before the last heartbeat.
+fn recover_crashed_session(database: &Mutex<Database>, live_state_path: &std::path::Path) {
+    let Ok(live_state) = LiveState::load(live_state_path) else {
+        return;
+    };
+    let Some((session_start, end)) = live_state.unfinished_session() else {
+        return;
+    };
+    let mut db = database.lock().unwrap();
+    if let Some(entry) = db.recover_session(session_start, end) {
+        println!(
+            "Recovered session {} -> {} of a monitoring process which did not end cleanly",
+            entry.start.format("%a %F %T"),
+            entry.end.format("%T")
+        );
+        db.store_file().unwrap();
+    }
+}
+
+/// Shared-folder sync with the other computers, if `sync_dir` is configured
+fn sync_with_other_hosts(
+    database: &Mutex<Database>,
+    cfg: &Config,
+    session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+    report: bool,
+) {
+    if cfg.sync_dir.is_empty() {
+        return;
+    }
+    let sync_dir = expanduser::expanduser(cfg.sync_dir.as_str()).unwrap();
+    let host_file = expanduser::expanduser(cfg.host_file.as_str()).unwrap();
+    let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+    let mut db = database.lock().unwrap();
+    let in_progress = session_start.map_or(Vec::new(), |start| db.in_progress_pieces(start, now));
+    match sync::sync_folder(&mut db, &sync_dir, &host_file, &cfg.host(), &in_progress) {
+        Ok(summary) => {
+            if report {
+                for (host, entries) in summary.added.iter() {
+                    println!("Merged {} entries from {}", entries.len(), host);
+                }
+                for err in summary.failed.iter() {
+                    eprintln!("Skipped in sync: {}", err);
+                }
+            }
+            if !summary.added.is_empty() {
+                db.store_file().unwrap();
+            }
+        }
+        Err(err) => {
+            if report {
+                eprintln!("Sync with {} failed: {}", sync_dir.display(), err);
+            }
+        }
+    }
+}
+
+/// Appends the new window usages to the app file. `finished` when no more usages are recorded.
+fn store_app_usages(app_recorder: &Mutex<AppRecorder>, app_file: &std::path::Path, finished: bool, report: bool) {
+    if let Err(err) = app_recorder.lock().unwrap().store(app_file, finished) {
+        if report {
+            eprintln!("Failed to store window usage: {}", err);
+        }
+    }
+}
+
+fn run_monitoring(database: Arc<Mutex<Database>>, cfg: &Config, output: Output) {
+    let cfg = Arc::new(RwLock::new(cfg.clone()));
+    let shutdown = Arc::new(Shutdown::default());
+    let live_state_path = expanduser::expanduser(cfg.read().unwrap().live_state_file.as_str()).unwrap();
+    recover_crashed_session(&database, &live_state_path);
+    sync_with_other_hosts(&database, &cfg.read().unwrap(), None, true);
+    let app_file = expanduser::expanduser(cfg.read().unwrap().app_file.as_str()).unwrap();
+    let app_recorder = Arc::new(Mutex::new(AppRecorder::new(
+        apps::read_app_usages(&app_file).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            Vec::new()
+        }),
+    )));
     let activity_recorder = Arc::new(Mutex::new(ActivityRecorder::new(
         database.clone(),
     )));
 
-    // Mutex used to syncronize auto-save with sigint handling (in order to avoid terminating in the middle of writing into file)
-    // Note: termination is a bit messy right now, as I do not cleanly join all threads, so I need hacks like this
-    let file_mutex_signal = Arc::new(Mutex::new(()));
-    let file_mutex_auto_save = file_mutex_signal.clone();
+    // Sessions are only recorded from the detector of the current generation. The generation is
+    // increased when the detector is replaced after a changed timeout and when monitoring ends.
+    let detector_generation = Arc::new(atomic::AtomicU64::new(0));
+    let activity_recorder_monitor = activity_recorder.clone();
+    let detector_generation_monitor = detector_generation.clone();
+    let start_idle_detector = move |timeout_minutes: u64, generation: u64| {
+        let activity_recorder_monitor = activity_recorder_monitor.clone();
+        let detector_generation_monitor = detector_generation_monitor.clone();
+        create_idle_detector(timeout_minutes).and_then(|mut detector| {
+            detector
+                .start_monitoring(Box::new(move |session| {
+                    let mut recorder = activity_recorder_monitor.lock().unwrap();
+                    if detector_generation_monitor.load(atomic::Ordering::SeqCst) == generation {
+                        recorder.handle_session(session);
+                    }
+                }))
+                .map_err(|e| format!("Failed to start idle monitoring: {:?}", e))?;
+            Ok(detector)
+        })
+    };
 
     // Create platform-specific idle detector and start monitoring
-    let mut idle_detector = create_idle_detector(cfg.timeout_minutes)
-        .unwrap_or_else(|e| {
-            eprintln!("Failed to create idle detector:\n{}", e);
-            std::process::exit(1);
-        });
-    
-    // Start idle detection monitoring
-    let activity_recorder_monitor = activity_recorder.clone();
-    idle_detector
-        .start_monitoring(Box::new(move |session| {
-            activity_recorder_monitor
-                .lock()
-                .unwrap()
-                .handle_session(session);
-        }))
-        .expect("Failed to start idle monitoring");
-    
+    let idle_detector = start_idle_detector(cfg.read().unwrap().timeout_minutes, 0).unwrap_or_else(|e| {
+        eprintln!("Failed to create idle detector:\n{}", e);
+        std::process::exit(1);
+    });
+
     // Store detector for signal handling
     let idle_detector_ref = Arc::new(Mutex::new(idle_detector));
 
-    // monitor signals:
-    let database_signals = database.clone();
+    // messages would mess up the terminal UI:
+    let report = output != Output::Tui;
+
+    // hooks of all threads run one after another:
+    let hook_worker = HookWorker::start(report);
+
+    // monitor signals: SIGHUP reloads the config, the others end monitoring
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGQUIT, SIGHUP]).unwrap();
+    let signals_handle = signals.handle();
+    let shutdown_signals = shutdown.clone();
+    let cfg_signals = cfg.clone();
     let idle_detector_signals = idle_detector_ref.clone();
     let activity_recorder_signals = activity_recorder.clone();
-    thread::spawn(move || {
-        let mut signals = Signals::new(&[SIGINT]).unwrap();
+    let detector_generation_signals = detector_generation.clone();
+    let signal_thread = thread::spawn(move || {
         for sig in signals.forever() {
-            println!("Received signal {:?}", sig);
-            
-            // Get current session and commit it
-            let detector = idle_detector_signals.lock().unwrap();
-            if let Some(session) = detector.get_current_session() {
-                activity_recorder_signals
-                    .lock()
-                    .unwrap()
-                    .commit_session(session);
-            }
-            
-            println!("Saving worktimes into data file...");
-            database_signals.lock().unwrap().store_file().unwrap();
-            // lock mutex here, which prevents any auto-save to try saving while we exit
-            let _lock = file_mutex_signal.lock();
-            std::process::exit(0);
+            if sig == SIGHUP {
+                if reload_config(&cfg_signals, report) {
+                    // a new detector with the changed timeout takes over, the running session is closed
+                    let timeout_minutes = cfg_signals.read().unwrap().timeout_minutes;
+                    let generation = detector_generation_signals.load(atomic::Ordering::SeqCst) + 1;
+                    match start_idle_detector(timeout_minutes, generation) {
+                        Ok(new_detector) => {
+                            let mut detector = idle_detector_signals.lock().unwrap();
+                            let mut recorder = activity_recorder_signals.lock().unwrap();
+                            detector_generation_signals.store(generation, atomic::Ordering::SeqCst);
+                            if let Some(session) = detector.get_current_session() {
+                                recorder.commit_session(session);
+                            }
+                            drop(recorder);
+                            *detector = new_detector;
+                            if report {
+                                println!("Idle detection restarted with a timeout of {} minutes", timeout_minutes);
+                            }
+                        }
+                        Err(err) => {
+                            if report {
+                                eprintln!("Failed to restart idle detection, keeping the old timeout: {}", err);
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+            if report {
+                println!("Received signal {:?}", sig);
+            }
+            shutdown_signals.request();
+            break;
         }
     });
 
+    // live state for `worktime status`, desktop notifications and crash recovery:
+    let database_live = database.clone();
+    let idle_detector_live = idle_detector_ref.clone();
+    let cfg_live = cfg.clone();
+    let shutdown_live = shutdown.clone();
+    let live_state_path_live = live_state_path.clone();
+    let app_recorder_live = app_recorder.clone();
+    let hooks_live = hook_worker.queue();
+    // the live state thread is the heart beat of monitoring, so it also feeds the systemd watchdog
+    let watchdog = output == Output::Daemon && daemon::watchdog_enabled();
+    let live_thread = thread::spawn(move || {
+        let mut notifier = Notifier::default();
+        let notification_worker = NotificationWorker::start(report);
+        let mut event_tracker = EventTracker::default();
+        let mut clock_jump_detector = ClockJumpDetector::default();
+        let mut window_tracker = WindowTracker::default();
+        loop {
+            let cfg = cfg_live.read().unwrap().clone();
+            let detector = idle_detector_live.lock().unwrap();
+            let session_start = detector.get_current_session().map(|s| s.start);
+            let idle_duration = detector.get_idle_duration();
+            drop(detector);
+
+            if session_start.is_some() {
+                window_tracker.sample(&cfg, report);
+            }
+            let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+            let mut db = database_live.lock().unwrap();
+            if let Some((jump_start, jump_end)) = clock_jump_detector.check(std::time::Instant::now(), now) {
+                if report {
+                    println!(
+                        "Clock jumped from {} to {} (system sleep or clock change)",
+                        jump_start.format("%a %F %T"),
+                        jump_end.format("%a %F %T")
+                    );
+                }
+                if let Some(session_start) = session_start {
+                    db.split_session_at_clock_jump(session_start, jump_start, jump_end);
+                }
+                app_recorder_live.lock().unwrap().interrupt();
+            }
+            window_tracker.update(&cfg, &mut app_recorder_live.lock().unwrap(), &mut db, session_start, now, report);
+            let live_state = LiveState::collect(&db, session_start, idle_duration, now);
+            let work_state = WorkState {
+                now,
+                session_start: live_state.session_start,
+                day_sum: chrono::Duration::seconds(live_state.day_seconds),
+                day_expected: db.get_expected_day_worktime(now.date_naive(), chrono::Duration::hours(cfg.weekly_hours)),
+                week_sum: chrono::Duration::seconds(live_state.week_seconds),
+            };
+            let events = event_tracker.update(&db, session_start, work_state.day_sum, work_state.day_expected, now);
+            drop(db);
+
+            for event in events {
+                hooks_live.fire(&cfg.hooks, event);
+            }
+
+            if let Err(err) = live_state.store(&live_state_path_live) {
+                if report {
+                    eprintln!("Failed to write live state: {}", err);
+                }
+            }
+            for trigger in notifier.due(&cfg, &work_state) {
+                notification_worker.send(trigger, &work_state, &cfg.duration_format(), cfg.locale);
+            }
+            if watchdog {
+                let _ = daemon::sd_notify("WATCHDOG=1");
+            }
+            if shutdown_live.wait(std::time::Duration::from_secs(2)) {
+                break;
+            }
+        }
+        notification_worker.stop();
+    });
+
     // auto-save:
     let database_autosave = database.clone();
-    let auto_save_interval_seconds = cfg.auto_save_interval_seconds.clone();
-    thread::spawn(move || loop {
-        thread::sleep(std::time::Duration::from_secs(auto_save_interval_seconds));
-        println!("Auto-Save");
-        let _lock = file_mutex_auto_save.lock();
-        database_autosave.lock().unwrap().store_file().unwrap();
+    let cfg_autosave = cfg.clone();
+    let shutdown_autosave = shutdown.clone();
+    let idle_detector_autosave = idle_detector_ref.clone();
+    let app_recorder_autosave = app_recorder.clone();
+    let app_file_autosave = app_file.clone();
+    let hooks_autosave = hook_worker.queue();
+    let autosave_thread = thread::spawn(move || {
+        let mut today = chrono::Local::now().date_naive();
+        loop {
+            let auto_save_interval_seconds = cfg_autosave.read().unwrap().auto_save_interval_seconds;
+            if shutdown_autosave.wait(std::time::Duration::from_secs(auto_save_interval_seconds)) {
+                break;
+            }
+            let cfg = cfg_autosave.read().unwrap().clone();
+
+            // end-of-day commit: fill breaks of the finished day from the calendar
+            let session_start = idle_detector_autosave.lock().unwrap().get_current_session().map(|s| s.start);
+            let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+            if now.date_naive() != today {
+                if cfg.calendar_auto_fill && !cfg.calendar_file.is_empty() {
+                    let range = (start_of_day(today), start_of_day(now.date_naive()));
+                    let mut db = database_autosave.lock().unwrap();
+                    // the running session is only stored when it ends, but its time is no break:
+                    let in_progress = session_start.map_or(Vec::new(), |start| db.in_progress_pieces(start, now));
+                    calendar::auto_fill_gaps(&mut db, &in_progress, &cfg.calendar_file, range);
+                }
+                today = now.date_naive();
+            }
+
+            if output == Output::Plain {
+                println!("Auto-Save");
+            }
+            database_autosave.lock().unwrap().store_file().unwrap();
+            if cfg.track_windows {
+                store_app_usages(&app_recorder_autosave, &app_file_autosave, false, report);
+            }
+            sync_with_other_hosts(&database_autosave, &cfg, session_start, report);
+            hooks_autosave.fire(&cfg.hooks, hooks::Event::AutoSaved);
+        }
     });
 
-    // monitor terminal input:
-    // TODO
-    let overtime_end: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
-    let overtime = database.lock().unwrap().calculate_overtime(
-        chrono::Duration::hours(cfg.weekly_hours),
-        (cfg.cutoff_datetime, overtime_end),
-    ) + chrono::Duration::seconds((cfg.cutoff_day_overtime_hours * 3600.0) as i64);
-    println!("overtime: {}", format_chrono_duration(&overtime));
-
-    loop {
-        thread::sleep(std::time::Duration::from_secs(2));
-        println!("---");
-        
-        // Get current in-progress session from the idle detector to show live duration
-        let detector = idle_detector_ref.lock().unwrap();
-        let current_session = detector.get_current_session();
-        let current_start = current_session.map(|s| s.start);
-        
-        // Get idle duration for live activity status
-        let idle_duration = detector.get_idle_duration();
-        drop(detector); // Release lock before printing
-        
-        database.lock().unwrap().print_vertical_timeline_with_current(current_start);
-        
-        // Display live activity state AFTER the timeline
-        if let Some(idle_dur) = idle_duration {
-            let idle_secs = idle_dur.num_seconds();
-            if idle_secs < 60 {
-                println!("Active ({}s since last input)", idle_secs);
+    let mut output = output;
+    if output == Output::Tui {
+        let idle_detector_tui = idle_detector_ref.clone();
+        let shutdown_tui = shutdown.clone();
+        let result = tui::run(
+            database.clone(),
+            &cfg,
+            move || {
+                let detector = idle_detector_tui.lock().unwrap();
+                (detector.get_current_session().map(|s| s.start), detector.get_idle_duration())
+            },
+            move || shutdown_tui.is_requested(),
+        );
+        if let Err(err) = result {
+            eprintln!("Terminal UI failed, falling back to plain output: {}", err);
+            output = Output::Plain;
+        }
+    }
+
+    if output == Output::Daemon {
+        if let Err(err) = daemon::sd_notify("READY=1") {
+            eprintln!("Failed to notify systemd: {}", err);
+        }
+        println!("Monitoring worktime");
+        while !shutdown.wait(std::time::Duration::from_secs(3600)) {}
+        let _ = daemon::sd_notify("STOPPING=1");
+    }
+
+    if output == Output::Plain {
+        // no terminal UI, just print the timeline periodically:
+        let overtime_end: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+        let cfg_plain = cfg.read().unwrap().clone();
+        let overtime = report::overtime(&database.lock().unwrap(), &cfg_plain, overtime_end);
+        println!("{}", cfg_plain.locale.format("overtime: {}", &[&cfg_plain.duration_format().format(&overtime)]));
+
+        while !shutdown.wait(std::time::Duration::from_secs(2)) {
+            println!("---");
+
+            // Get current in-progress session from the idle detector to show live duration
+            let detector = idle_detector_ref.lock().unwrap();
+            let current_session = detector.get_current_session();
+            let current_start = current_session.map(|s| s.start);
+
+            // Get idle duration for live activity status
+            let idle_duration = detector.get_idle_duration();
+            drop(detector); // Release lock before printing
+
+            let db = database.lock().unwrap();
+            let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+            let current_start = current_start.and_then(|start| db.effective_session_start(start, now));
+            let day_report = report::DayReport::collect(
+                &db,
+                now.date_naive(),
+                chrono::Duration::hours(cfg_plain.weekly_hours),
+                now,
+                current_start,
+            );
+            drop(db);
+            print!(
+                "{}",
+                report::render_day(report::Format::detect(), &cfg_plain.duration_format(), cfg_plain.locale, &day_report)
+            );
+
+            // Display live activity state AFTER the timeline
+            if let Some(idle_dur) = idle_duration {
+                let idle_secs = idle_dur.num_seconds();
+                if idle_secs < 60 {
+                    println!("{}", cfg_plain.locale.format("Active ({}s since last input)", &[&idle_secs]));
+                } else {
+                    let idle_mins = idle_secs / 60;
+                    println!("{}", cfg_plain.locale.format("Idle for {}m {}s", &[&idle_mins, &(idle_secs % 60)]));
+                }
             } else {
-                let idle_mins = idle_secs / 60;
-                println!("Idle for {}m {}s", idle_mins, idle_secs % 60);
+                println!("{}", cfg_plain.locale.tr("No activity detected yet"));
             }
-        } else {
-            println!("No activity detected yet");
         }
     }
-}
\ No newline at end of file
+
+    // stop all threads first, so nothing writes the data file during the final save
+    shutdown.request();
+    signals_handle.close();
+    signal_thread.join().unwrap();
+    live_thread.join().unwrap();
+    autosave_thread.join().unwrap();
+    hook_worker.stop();
+
+    // Get current session and commit it
+    let cfg = cfg.read().unwrap().clone();
+    let detector = idle_detector_ref.lock().unwrap();
+    let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+    let closed_session = detector.get_current_session().map(|session| hooks::Event::SessionClosed {
+        start: session.start,
+        end: now,
+        duration: database
+            .lock()
+            .unwrap()
+            .in_progress_pieces(session.start, now)
+            .iter()
+            .fold(chrono::Duration::zero(), |sum, piece| sum + piece.duration()),
+    });
+    // stop recording sessions of the detector, so none is committed after the final one
+    let mut recorder = activity_recorder.lock().unwrap();
+    detector_generation.fetch_add(1, atomic::Ordering::SeqCst);
+    if let Some(session) = detector.get_current_session() {
+        recorder.commit_session(session);
+    }
+    drop(recorder);
+    drop(detector);
+
+    if cfg.calendar_auto_fill && !cfg.calendar_file.is_empty() {
+        let range = (start_of_day(now.date_naive()), now);
+        calendar::auto_fill_gaps(&mut database.lock().unwrap(), &[], &cfg.calendar_file, range);
+    }
+
+    println!("Saving worktimes into data file...");
+    database.lock().unwrap().store_file().unwrap();
+    if cfg.track_windows {
+        store_app_usages(&app_recorder, &app_file, true, true);
+    }
+    sync_with_other_hosts(&database, &cfg, None, true);
+    if let Some(event) = closed_session {
+        for error in hooks::run_all(&cfg.hooks, &event, true) {
+            eprintln!("{}", error);
+        }
+    }
+    let _ = std::fs::remove_file(&live_state_path);
+}
@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::sync::mpsc;
+use crate::config::Config;
+use crate::duration::DurationFormat;
+use crate::locale::Locale;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    /// the running session is longer than `notify_break_after_minutes`
+    BreakReminder,
+    /// today's expected worktime is reached
+    DayTarget,
+    /// today's worktime reached `notify_day_limit_hours`
+    DayLimit,
+    /// `weekly_hours` are reached
+    WeekTarget,
+}
+
+/// What the monitor knows about the current work
+pub struct WorkState {
+    pub now: chrono::DateTime<chrono::offset::Local>,
+    /// start of the running session without paused time, if any
+    pub session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+    pub day_sum: chrono::Duration,
+    pub day_expected: chrono::Duration,
+    pub week_sum: chrono::Duration,
+}
+
+/// Decides which notifications are due. Each one is sent once per session, day or week.
+#[derive(Default)]
+pub struct Notifier {
+    sent: HashSet<(Trigger, String)>,
+}
+
+impl Notifier {
+    /// Returns the triggers which fired since the last call
+    pub fn due(self: &mut Self, cfg: &Config, state: &WorkState) -> Vec<Trigger> {
+        let day = state.now.date_naive().to_string();
+        let week = state.now.date_naive().week(cfg.locale.first_weekday()).first_day().to_string();
+        let mut candidates = Vec::new();
+        if cfg.notify_break_reminder {
+            if let Some(session_start) = state.session_start {
+                if state.now - session_start >= chrono::Duration::minutes(cfg.notify_break_after_minutes) {
+                    candidates.push((Trigger::BreakReminder, session_start.to_rfc3339()));
+                }
+            }
+        }
+        if cfg.notify_day_target && state.day_expected > chrono::Duration::zero() && state.day_sum >= state.day_expected {
+            candidates.push((Trigger::DayTarget, day.clone()));
+        }
+        let day_limit = chrono::Duration::seconds((cfg.notify_day_limit_hours * 3600.0) as i64);
+        if cfg.notify_day_limit && state.day_sum >= day_limit {
+            candidates.push((Trigger::DayLimit, day));
+        }
+        if cfg.notify_week_target && state.week_sum >= chrono::Duration::hours(cfg.weekly_hours) {
+            candidates.push((Trigger::WeekTarget, week));
+        }
+        candidates
+            .into_iter()
+            .filter(|candidate| self.sent.insert(candidate.clone()))
+            .map(|(trigger, _)| trigger)
+            .collect()
+    }
+}
+
+fn message(trigger: Trigger, state: &WorkState, durations: &DurationFormat, locale: Locale) -> (String, String) {
+    let (summary, body) = match trigger {
+        Trigger::BreakReminder => (
+            "Time for a break",
+            locale.format(
+                "You've worked {} without a break",
+                &[&durations.format(&(state.now - state.session_start.unwrap_or(state.now)))],
+            ),
+        ),
+        Trigger::DayTarget => (
+            "Day target reached",
+            locale.format("You reached today's expected {}", &[&durations.format(&state.day_expected)]),
+        ),
+        Trigger::DayLimit => (
+            "Stop working",
+            locale.format("You're at {} today", &[&durations.format(&state.day_sum)]),
+        ),
+        Trigger::WeekTarget => (
+            "Weekly target reached",
+            locale.format("You worked {} this week", &[&durations.format(&state.week_sum)]),
+        ),
+    };
+    (locale.tr(summary).to_string(), body)
+}
+
+/// Shows a desktop notification through the freedesktop notification service
+fn show(summary: &str, body: &str) -> Result<(), String> {
+    notify_rust::Notification::new()
+        .appname("worktime")
+        .summary(summary)
+        .body(body)
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Sends notifications from its own thread. Without a notification service on the session bus
+/// the D-Bus call can block for a long time, which must not delay the monitoring loop.
+pub struct NotificationWorker {
+    sender: mpsc::Sender<(String, String)>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl NotificationWorker {
+    pub fn start(report_errors: bool) -> Self {
+        Self::start_with(show, report_errors)
+    }
+
+    fn start_with(show: impl Fn(&str, &str) -> Result<(), String> + Send + 'static, report_errors: bool) -> Self {
+        let (sender, receiver) = mpsc::channel::<(String, String)>();
+        let thread = std::thread::spawn(move || {
+            for (summary, body) in receiver {
+                if let Err(err) = show(&summary, &body) {
+                    if report_errors {
+                        eprintln!("Failed to send notification: {}", err);
+                    }
+                }
+            }
+        });
+        NotificationWorker { sender, thread }
+    }
+
+    /// Queues the notification of `trigger` in the language of `locale`, without waiting for it to be shown
+    pub fn send(self: &Self, trigger: Trigger, state: &WorkState, durations: &DurationFormat, locale: Locale) {
+        let _ = self.sender.send(message(trigger, state, durations, locale));
+    }
+
+    /// Sends the queued notifications and ends the thread
+    pub fn stop(self: Self) {
+        drop(self.sender);
+        self.thread.join().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn work_state(now: &str, session_start: Option<&str>, day_sum_hours: i64, week_sum_hours: i64) -> WorkState {
+        WorkState {
+            now: now.parse().unwrap(),
+            session_start: session_start.map(|start| start.parse().unwrap()),
+            day_sum: chrono::Duration::hours(day_sum_hours),
+            day_expected: chrono::Duration::hours(8),
+            week_sum: chrono::Duration::hours(week_sum_hours),
+        }
+    }
+
+    #[test]
+    fn test_notifier_fires_once() {
+        let cfg = Config {
+            notify_break_reminder: true,
+            notify_day_target: true,
+            notify_day_limit: true,
+            notify_week_target: true,
+            weekly_hours: 40,
+            ..Default::default()
+        };
+        let mut notifier = Notifier::default();
+
+        let state = work_state("2023-05-03T10:00:00+02:00", Some("2023-05-03T08:00:00+02:00"), 2, 18);
+        assert_eq!(notifier.due(&cfg, &state), vec![]);
+
+        let state = work_state("2023-05-03T11:00:00+02:00", Some("2023-05-03T08:00:00+02:00"), 3, 19);
+        assert_eq!(notifier.due(&cfg, &state), vec![Trigger::BreakReminder]);
+        assert_eq!(notifier.due(&cfg, &state), vec![]);
+
+        // a new session after a break is reminded again
+        let state = work_state("2023-05-03T18:30:00+02:00", Some("2023-05-03T15:00:00+02:00"), 10, 27);
+        assert_eq!(
+            notifier.due(&cfg, &state),
+            vec![Trigger::BreakReminder, Trigger::DayTarget, Trigger::DayLimit]
+        );
+
+        // next day of the same week
+        let state = work_state("2023-05-04T17:00:00+02:00", None, 8, 40);
+        assert_eq!(notifier.due(&cfg, &state), vec![Trigger::DayTarget, Trigger::WeekTarget]);
+    }
+
+    #[test]
+    fn test_worker_does_not_block() {
+        let shown = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let shown_worker = shown.clone();
+        let worker = NotificationWorker::start_with(
+            move |summary, _| {
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                shown_worker.lock().unwrap().push(summary.to_string());
+                Ok(())
+            },
+            false,
+        );
+        let state = work_state("2023-05-03T17:00:00+02:00", None, 8, 40);
+        let sent = std::time::Instant::now();
+        worker.send(Trigger::DayTarget, &state, &DurationFormat::default(), Locale::En);
+        worker.send(Trigger::WeekTarget, &state, &DurationFormat::default(), Locale::De);
+        assert!(sent.elapsed() < std::time::Duration::from_millis(100));
+        worker.stop();
+        assert_eq!(*shown.lock().unwrap(), vec!["Day target reached", "Wochenziel erreicht"]);
+    }
+
+    /// Records the notifications it receives, like a notification daemon would show them
+    struct MockNotificationDaemon {
+        received: std::sync::Arc<std::sync::Mutex<Vec<(String, String, String)>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl MockNotificationDaemon {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            app_name: String,
+            _replaces_id: u32,
+            _app_icon: String,
+            summary: String,
+            body: String,
+            _actions: Vec<String>,
+            _hints: std::collections::HashMap<String, zbus::zvariant::OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let mut received = self.received.lock().unwrap();
+            received.push((app_name, summary, body));
+            received.len() as u32
+        }
+    }
+
+    #[test]
+    fn test_send_to_mock_notification_daemon() {
+        // a private session bus, so the test neither needs nor disturbs a desktop session
+        let Ok(mut bus) = std::process::Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+        else {
+            eprintln!("dbus-daemon not found, skipping the test");
+            return;
+        };
+        let mut address = String::new();
+        std::io::BufRead::read_line(&mut std::io::BufReader::new(bus.stdout.take().unwrap()), &mut address).unwrap();
+        let address = address.trim().to_string();
+
+        let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let daemon = zbus::blocking::connection::Builder::address(address.as_str())
+            .unwrap()
+            .name("org.freedesktop.Notifications")
+            .unwrap()
+            .serve_at("/org/freedesktop/Notifications", MockNotificationDaemon { received: received.clone() })
+            .unwrap()
+            .build()
+            .unwrap();
+        // notify-rust connects to the session bus of this variable
+        std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &address);
+
+        let worker = NotificationWorker::start(false);
+        let state = work_state("2023-05-03T11:00:00+02:00", Some("2023-05-03T08:00:00+02:00"), 3, 19);
+        worker.send(Trigger::BreakReminder, &state, &DurationFormat::default(), Locale::En);
+        worker.stop();
+        drop(daemon);
+        bus.kill().unwrap();
+        bus.wait().unwrap();
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![(
+                "worktime".to_string(),
+                "Time for a break".to_string(),
+                "You've worked 3h0m0s without a break".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_notifier_disabled_triggers() {
+        let cfg = Config {
+            notify_day_limit: true,
+            ..Default::default()
+        };
+        let mut notifier = Notifier::default();
+        let state = work_state("2023-05-03T20:00:00+02:00", Some("2023-05-03T08:00:00+02:00"), 12, 50);
+        assert_eq!(notifier.due(&cfg, &state), vec![Trigger::DayLimit]);
+    }
+}
@@ -0,0 +1,670 @@
+use std::io::IsTerminal;
+use chrono::{Datelike, Timelike};
+use crate::billing::BillingRounding;
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{SpecialDayType, WorktimeEntry};
+use crate::duration::DurationFormat;
+use crate::locale::Locale;
+use crate::utils::{escape_html, start_of_day};
+
+/// Accumulated overtime until `end`, including the overtime configured for the cutoff day
+/// Worktime is counted as billed, if `billing_overtime` is set.
+pub fn overtime(db: &Database, cfg: &Config, end: chrono::DateTime<chrono::offset::Local>) -> chrono::Duration {
+    let billing = cfg.billing().filter(|_| cfg.billing_overtime);
+    db.calculate_billed_overtime(chrono::Duration::hours(cfg.weekly_hours), (cfg.cutoff_datetime, end), billing.as_ref())
+        + chrono::Duration::seconds((cfg.cutoff_day_overtime_hours * 3600.0) as i64)
+}
+
+/// Output format of reports
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Plain,
+    /// text with ANSI colors
+    Color,
+    Json,
+    Markdown,
+    Html,
+}
+
+impl Format {
+    /// Colored text on a terminal, plain text if stdout is redirected or `NO_COLOR` is set
+    pub fn detect() -> Self {
+        Self::text(std::io::stdout().is_terminal(), std::env::var_os("NO_COLOR"))
+    }
+
+    pub fn text(is_terminal: bool, no_color: Option<std::ffi::OsString>) -> Self {
+        // see https://no-color.org: only a non-empty value disables colors
+        if is_terminal && no_color.is_none_or(|value| value.is_empty()) {
+            Format::Color
+        } else {
+            Format::Plain
+        }
+    }
+}
+
+/// Worktime of a day
+#[derive(Debug, PartialEq)]
+pub struct DayReport {
+    pub day: chrono::NaiveDate,
+    pub now: chrono::DateTime<chrono::offset::Local>,
+    /// including the part of the running session on this day, if it continued into a later day
+    pub entries: Vec<WorktimeEntry>,
+    /// start of the running session, if it is running on this day. Midnight, if it started before.
+    pub running_since: Option<chrono::DateTime<chrono::offset::Local>>,
+    /// including the running session
+    pub worked: chrono::Duration,
+    /// worktime of the week until this day's end, including the running session
+    pub week_worked: chrono::Duration,
+    pub expected: chrono::Duration,
+    /// why nothing is expected, e.g. `Weekend` or `Vacation`
+    pub reason: Option<String>,
+    /// worktime as billed, including the running session. Only set by `bill`
+    pub billed: Option<chrono::Duration>,
+}
+
+impl DayReport {
+    /// `current_session_start` is the start of the running session, it is counted until `now`
+    pub fn collect(
+        db: &Database,
+        day: chrono::NaiveDate,
+        weekly_worktime: chrono::Duration,
+        now: chrono::DateTime<chrono::offset::Local>,
+        current_session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+    ) -> Self {
+        let day_bounds = Database::get_day_bounds(start_of_day(day));
+        let (week_start, _) = db.get_week_bounds(start_of_day(day));
+        // the running session, cut to the given bounds
+        let running_within = |bounds: (chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>)| {
+            current_session_start
+                .map(|start| (std::cmp::max(start, bounds.0), std::cmp::min(now, bounds.1)))
+                .filter(|(start, end)| start < end)
+        };
+        let running_on_day = running_within(day_bounds);
+        let running_since = running_on_day.filter(|(_, end)| *end == now).map(|(start, _)| start);
+        let mut entries: Vec<WorktimeEntry> = db.query(day_bounds).collect();
+        if let Some((start, end)) = running_on_day.filter(|(_, end)| *end < now) {
+            // the session continued into the next day, so its part on this day is finished
+            entries.push(WorktimeEntry {
+                start,
+                end,
+                comments: String::new(),
+            });
+        }
+        let mut worked = entries
+            .iter()
+            .fold(chrono::Duration::zero(), |sum, entry| sum + entry.duration());
+        if let Some(session_start) = running_since {
+            worked = worked + (now - session_start);
+        }
+        let mut week_worked = db
+            .query((week_start, day_bounds.1))
+            .fold(chrono::Duration::zero(), |sum, entry| sum + entry.duration());
+        if let Some((start, end)) = running_within((week_start, day_bounds.1)) {
+            week_worked = week_worked + (end - start);
+        }
+
+        let expected = db.get_expected_day_worktime(day, weekly_worktime);
+        let special = db.get_special_day(day).filter(|sd| {
+            matches!(sd.day_type, SpecialDayType::Vacation | SpecialDayType::Sick | SpecialDayType::Holiday)
+        });
+        let reason = if expected != chrono::Duration::zero() {
+            None
+        } else if let Some(special) = special {
+            Some(format!("{:?}", special.day_type))
+        } else if matches!(day.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun) {
+            Some("Weekend".to_string())
+        } else {
+            None
+        };
+        Self {
+            day,
+            now,
+            entries,
+            running_since,
+            worked,
+            week_worked,
+            expected,
+            reason,
+            billed: None,
+        }
+    }
+
+    /// Adds the worktime as billed by `billing`
+    pub fn bill(self: &mut Self, billing: &BillingRounding) {
+        let mut entries = self.entries.clone();
+        if let Some(session_start) = self.running_since {
+            entries.push(WorktimeEntry {
+                start: session_start,
+                end: self.now,
+                comments: String::new(),
+            });
+        }
+        self.billed = Some(billing.billed(&entries));
+    }
+
+    /// Reports of the last `num_days` days until `now`, latest first
+    pub fn collect_last(
+        db: &Database,
+        num_days: u64,
+        weekly_worktime: chrono::Duration,
+        now: chrono::DateTime<chrono::offset::Local>,
+    ) -> Vec<Self> {
+        (0..num_days)
+            .map(|i| Self::collect(db, now.date_naive() - chrono::Duration::days(i as i64), weekly_worktime, now, None))
+            .collect()
+    }
+
+    pub fn deviation(self: &Self) -> chrono::Duration {
+        self.worked - self.expected
+    }
+}
+
+/// Worktime of a week, from its first day (monday or sunday, see `Database::get_week_start`)
+#[derive(Debug, PartialEq)]
+pub struct WeekReport {
+    pub first_day: chrono::NaiveDate,
+    pub days: Vec<DayReport>,
+    pub worked: chrono::Duration,
+    pub expected: chrono::Duration,
+    /// sum of the days as billed. Only set by `bill`
+    pub billed: Option<chrono::Duration>,
+}
+
+impl WeekReport {
+    /// The week containing `day`
+    pub fn collect(
+        db: &Database,
+        day: chrono::NaiveDate,
+        weekly_worktime: chrono::Duration,
+        now: chrono::DateTime<chrono::offset::Local>,
+        current_session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+    ) -> Self {
+        let first_day = db.get_week_start(day);
+        let days: Vec<DayReport> = (0..7)
+            .map(|i| {
+                DayReport::collect(db, first_day + chrono::Duration::days(i), weekly_worktime, now, current_session_start)
+            })
+            .collect();
+        Self {
+            first_day,
+            worked: days.iter().fold(chrono::Duration::zero(), |sum, day| sum + day.worked),
+            expected: days.iter().fold(chrono::Duration::zero(), |sum, day| sum + day.expected),
+            days,
+            billed: None,
+        }
+    }
+
+    /// Adds the worktime as billed by `billing`, per day
+    pub fn bill(self: &mut Self, billing: &BillingRounding) {
+        let mut billed = chrono::Duration::zero();
+        for day in self.days.iter_mut() {
+            day.bill(billing);
+            billed = billed + day.billed.unwrap();
+        }
+        self.billed = Some(billed);
+    }
+
+    pub fn deviation(self: &Self) -> chrono::Duration {
+        self.worked - self.expected
+    }
+}
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const ORANGE: &str = "\x1b[38;5;208m";
+const RED: &str = "\x1b[31m";
+const GREY: &str = "\x1b[38;5;250m";
+const RESET: &str = "\x1b[0m";
+
+fn paint(text: &str, color_code: &str, color: bool) -> String {
+    if color && !text.is_empty() {
+        format!("{}{}{}", color_code, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// A line with `marker` for every quarter hour between start and end
+pub fn format_filler(
+    start: chrono::DateTime<chrono::offset::Local>,
+    end: chrono::DateTime<chrono::offset::Local>,
+    marker: &str,
+) -> String {
+    let mut result = String::new();
+    // Round up to the next 15-min mark
+    let minute = start.time().minute();
+    let add_minutes = (15 - (minute % 15)) % 15;
+    let mut mark_time = start + chrono::Duration::minutes(add_minutes as i64);
+    while mark_time < end {
+        result.push_str(&format!("| {} {}\n", mark_time.format("%H:%M"), marker));
+        mark_time = mark_time + chrono::Duration::minutes(15);
+    }
+    result
+}
+
+/// `worked`, followed by the billed time if it is known
+fn format_worked(
+    worked: &chrono::Duration,
+    billed: Option<chrono::Duration>,
+    durations: &DurationFormat,
+    locale: Locale,
+) -> String {
+    match billed {
+        Some(billed) => locale.format("{} (billed {})", &[&durations.format(worked), &durations.format(&billed)]),
+        None => durations.format(worked),
+    }
+}
+
+fn format_sums(report: &DayReport, durations: &DurationFormat, locale: Locale) -> String {
+    locale.format(
+        "Current: Day: {}, Week: {}",
+        &[
+            &format_worked(&report.worked, report.billed, durations, locale),
+            &durations.format(&report.week_worked),
+        ],
+    ) + "\n"
+}
+
+fn format_start_working(
+    start: chrono::DateTime<chrono::offset::Local>,
+    previous_end: Option<chrono::DateTime<chrono::offset::Local>>,
+    durations: &DurationFormat,
+    locale: Locale,
+    color: bool,
+) -> String {
+    match previous_end {
+        Some(previous_end) => format!(
+            "{}{}\n",
+            paint(&format_filler(previous_end, start, ""), GREY, color),
+            paint(
+                &locale.format(
+                    "| {} Start working (after {} break)",
+                    &[&start.format("%T"), &durations.format(&(start - previous_end))],
+                ),
+                GREEN,
+                color
+            )
+        ),
+        None => format!(
+            "{}\n",
+            paint(&locale.format("| {} Start working", &[&start.format("%T")]), GREEN, color)
+        ),
+    }
+}
+
+/// Timeline of the day with a line per quarter hour
+pub fn format_vertical_timeline(report: &DayReport, durations: &DurationFormat, locale: Locale, color: bool) -> String {
+    let mut result = String::new();
+    let mut previous_end = None;
+    for entry in report.entries.iter() {
+        result.push_str(&format_start_working(entry.start, previous_end, durations, locale, color));
+        let stopped = format_filler(entry.start, entry.end, "X")
+            + &locale.format(
+                "| {} Stopped working (after {})",
+                &[&entry.end.format("%T"), &durations.format(&entry.duration())],
+            );
+        result.push_str(&paint(&stopped, GREEN, color));
+        result.push('\n');
+        previous_end = Some(entry.end);
+    }
+
+    if let Some(session_start) = report.running_since {
+        result.push_str(&format_start_working(session_start, previous_end, durations, locale, color));
+        let working = format_filler(session_start, report.now, "~")
+            + &locale.format(
+                "| {} Working... ({})",
+                &[&report.now.format("%T"), &durations.format(&(report.now - session_start))],
+            );
+        result.push_str(&paint(&working, YELLOW, color));
+        result.push('\n');
+    }
+    result + &format_sums(report, durations, locale)
+}
+
+/// One line with the deviation from the expected worktime, colored by how far it is off
+pub fn format_day_line(report: &DayReport, durations: &DurationFormat, locale: Locale, color: bool) -> String {
+    let deviation_secs = report.deviation().num_seconds();
+    let color_code = if deviation_secs > 0 {
+        GREEN
+    } else if deviation_secs < -3 * 60 * 60 {
+        RED
+    } else if deviation_secs < -60 * 60 {
+        ORANGE
+    } else {
+        YELLOW
+    };
+    locale.format(
+        "{}: {}  deviation: {}",
+        &[
+            &locale.day(report.day),
+            &format_worked(&report.worked, report.billed, durations, locale),
+            &paint(&durations.format(&report.deviation()), color_code, color),
+        ],
+    ) + &report.reason.as_ref().map_or(String::new(), |reason| format!(" ({})", locale.tr(reason)))
+}
+
+fn day_json(report: &DayReport) -> serde_json::Value {
+    serde_json::json!({
+        "day": report.day.to_string(),
+        "worked_seconds": report.worked.num_seconds(),
+        "expected_seconds": report.expected.num_seconds(),
+        "week_worked_seconds": report.week_worked.num_seconds(),
+        "billed_seconds": report.billed.map(|billed| billed.num_seconds()),
+        "reason": report.reason,
+        "running_since": report.running_since.map(|start| start.to_rfc3339()),
+        "entries": report.entries.iter().map(|entry| serde_json::json!({
+            "start": entry.start.to_rfc3339(),
+            "end": entry.end.to_rfc3339(),
+            "duration_seconds": entry.duration().num_seconds(),
+            "comments": entry.comments,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn html_document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n{}</body>\n</html>\n",
+        escape_html(title),
+        escape_html(title),
+        body
+    )
+}
+
+/// Entries of the day with start, end, duration and comment, the running session last
+fn day_rows(report: &DayReport, durations: &DurationFormat, locale: Locale) -> Vec<[String; 4]> {
+    let mut rows: Vec<[String; 4]> = report
+        .entries
+        .iter()
+        .map(|entry| {
+            [
+                entry.start.format("%T").to_string(),
+                entry.end.format("%T").to_string(),
+                durations.format(&entry.duration()),
+                entry.comments.clone(),
+            ]
+        })
+        .collect();
+    if let Some(session_start) = report.running_since {
+        rows.push([
+            session_start.format("%T").to_string(),
+            String::new(),
+            locale.format("{} (running)", &[&durations.format(&(report.now - session_start))]),
+            String::new(),
+        ]);
+    }
+    rows
+}
+
+fn day_summary(report: &DayReport, durations: &DurationFormat, locale: Locale) -> String {
+    locale.format(
+        "Worked: {} of {}, week: {}",
+        &[
+            &format_worked(&report.worked, report.billed, durations, locale),
+            &durations.format(&report.expected),
+            &durations.format(&report.week_worked),
+        ],
+    )
+}
+
+fn week_rows(report: &WeekReport, durations: &DurationFormat, locale: Locale) -> Vec<[String; 5]> {
+    report
+        .days
+        .iter()
+        .map(|day| {
+            [
+                locale.day(day.day),
+                format_worked(&day.worked, day.billed, durations, locale),
+                durations.format(&day.expected),
+                durations.format(&day.deviation()),
+                day.reason.as_ref().map_or(String::new(), |reason| locale.tr(reason).to_string()),
+            ]
+        })
+        .collect()
+}
+
+fn week_summary(report: &WeekReport, durations: &DurationFormat, locale: Locale) -> String {
+    locale.format(
+        "Week: {} of {}, deviation: {}",
+        &[
+            &format_worked(&report.worked, report.billed, durations, locale),
+            &durations.format(&report.expected),
+            &durations.format(&report.deviation()),
+        ],
+    )
+}
+
+fn markdown_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> String {
+    let mut result = format!("| {} |\n|{}\n", header.join(" | "), "---|".repeat(N));
+    for row in rows {
+        let cells: Vec<String> = row.iter().map(|cell| escape_markdown(cell)).collect();
+        result.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    result
+}
+
+fn html_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> String {
+    let mut result = String::from("<table>\n<tr>");
+    for cell in header {
+        result.push_str(&format!("<th>{}</th>", escape_html(cell)));
+    }
+    result.push_str("</tr>\n");
+    for row in rows {
+        result.push_str("<tr>");
+        for cell in row {
+            result.push_str(&format!("<td>{}</td>", escape_html(cell)));
+        }
+        result.push_str("</tr>\n");
+    }
+    result + "</table>\n"
+}
+
+const DAY_HEADER: [&str; 4] = ["Start", "End", "Duration", "Comment"];
+const WEEK_HEADER: [&str; 5] = ["Day", "Worked", "Expected", "Deviation", "Note"];
+
+pub fn render_day(format: Format, durations: &DurationFormat, locale: Locale, report: &DayReport) -> String {
+    let title = locale.day(report.day);
+    let header = DAY_HEADER.map(|cell| locale.tr(cell));
+    match format {
+        Format::Plain => format_vertical_timeline(report, durations, locale, false),
+        Format::Color => format_vertical_timeline(report, durations, locale, true),
+        Format::Json => day_json(report).to_string() + "\n",
+        Format::Markdown => format!(
+            "## {}\n\n{}\n{}\n",
+            title,
+            markdown_table(header, &day_rows(report, durations, locale)),
+            day_summary(report, durations, locale)
+        ),
+        Format::Html => html_document(
+            &title,
+            &format!(
+                "{}<p>{}</p>\n",
+                html_table(header, &day_rows(report, durations, locale)),
+                escape_html(&day_summary(report, durations, locale))
+            ),
+        ),
+    }
+}
+
+pub fn render_week(format: Format, durations: &DurationFormat, locale: Locale, report: &WeekReport) -> String {
+    let title = locale.format("Week from {}", &[&locale.day(report.first_day)]);
+    let header = WEEK_HEADER.map(|cell| locale.tr(cell));
+    match format {
+        Format::Plain | Format::Color => {
+            let mut result = String::new();
+            for day in report.days.iter() {
+                result.push_str(&format_day_line(day, durations, locale, format == Format::Color));
+                result.push('\n');
+            }
+            result + &week_summary(report, durations, locale) + "\n"
+        }
+        Format::Json => {
+            serde_json::json!({
+                "first_day": report.first_day.to_string(),
+                "worked_seconds": report.worked.num_seconds(),
+                "expected_seconds": report.expected.num_seconds(),
+                "billed_seconds": report.billed.map(|billed| billed.num_seconds()),
+                "days": report.days.iter().map(day_json).collect::<Vec<_>>(),
+            })
+            .to_string()
+                + "\n"
+        }
+        Format::Markdown => format!(
+            "## {}\n\n{}\n{}\n",
+            title,
+            markdown_table(header, &week_rows(report, durations, locale)),
+            week_summary(report, durations, locale)
+        ),
+        Format::Html => html_document(
+            &title,
+            &format!(
+                "{}<p>{}</p>\n",
+                html_table(header, &week_rows(report, durations, locale)),
+                escape_html(&week_summary(report, durations, locale))
+            ),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{SpecialDayEntry, SpecialDayType};
+
+    fn at(time: &str) -> chrono::DateTime<chrono::offset::Local> {
+        time.parse().unwrap()
+    }
+
+    fn db() -> Database {
+        Database::in_memory(
+            vec![
+                WorktimeEntry {
+                    start: at("2023-05-02T08:00:00+02:00"),
+                    end: at("2023-05-02T12:00:00+02:00"),
+                    comments: "".into(),
+                },
+                WorktimeEntry {
+                    start: at("2023-05-03T08:00:00+02:00"),
+                    end: at("2023-05-03T09:00:00+02:00"),
+                    comments: "review <b> | merge".into(),
+                },
+            ],
+            vec![SpecialDayEntry {
+                day: "2023-05-04".parse().unwrap(),
+                day_type: SpecialDayType::Vacation,
+            }],
+        )
+    }
+
+    #[test]
+    fn test_day_report_with_running_session() {
+        let now = at("2023-05-03T10:00:00+02:00");
+        let report = DayReport::collect(
+            &db(),
+            now.date_naive(),
+            chrono::Duration::hours(40),
+            now,
+            Some(at("2023-05-03T09:30:00+02:00")),
+        );
+        assert_eq!(report.entries.len(), 1);
+        assert_eq!(report.worked, chrono::Duration::minutes(90));
+        assert_eq!(report.week_worked, chrono::Duration::minutes(330));
+        assert_eq!(report.expected, chrono::Duration::hours(8));
+        let durations = DurationFormat::default();
+
+        let plain = render_day(Format::Plain, &durations, Locale::En, &report);
+        assert!(plain.contains("| 08:15 X\n"));
+        assert!(plain.contains("| 09:30:00 Start working (after 0h30m0s break)\n"));
+        assert!(plain.contains("| 09:45 ~\n| 10:00:00 Working... (0h30m0s)\n"));
+        assert!(!plain.contains('\x1b'));
+        assert!(render_day(Format::Color, &durations, Locale::En, &report).contains("\x1b[33m| 09:30 ~\n| 09:45 ~\n| 10:00:00 Working... (0h30m0s)\x1b[0m\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&render_day(Format::Json, &durations, Locale::En, &report)).unwrap();
+        assert_eq!(json["worked_seconds"], 5400);
+        assert_eq!(json["entries"][0]["comments"], "review <b> | merge");
+        assert!(render_day(Format::Markdown, &durations, Locale::En, &report).contains("| 08:00:00 | 09:00:00 | 1h0m0s | review <b> \\| merge |\n"));
+        assert!(render_day(Format::Html, &durations, Locale::En, &report).contains("<td>review &lt;b&gt; | merge</td>"));
+    }
+
+    #[test]
+    fn test_reports_with_session_running_over_midnight() {
+        let db = Database::in_memory(
+            vec![WorktimeEntry {
+                start: at("2023-05-02T08:00:00+02:00"),
+                end: at("2023-05-02T12:00:00+02:00"),
+                comments: "".into(),
+            }],
+            vec![],
+        );
+        let now = at("2023-05-03T01:00:00+02:00");
+        let session_start = Some(at("2023-05-02T22:00:00+02:00"));
+        let today = DayReport::collect(&db, now.date_naive(), chrono::Duration::hours(40), now, session_start);
+        assert_eq!(today.running_since, Some(at("2023-05-03T00:00:00+02:00")));
+        assert_eq!(today.worked, chrono::Duration::hours(1));
+        assert_eq!(today.week_worked, chrono::Duration::hours(7));
+
+        let yesterday = "2023-05-02".parse().unwrap();
+        let yesterday = DayReport::collect(&db, yesterday, chrono::Duration::hours(40), now, session_start);
+        assert_eq!(yesterday.running_since, None);
+        assert_eq!(yesterday.entries.len(), 2);
+        assert_eq!(yesterday.entries[1].end, at("2023-05-03T00:00:00+02:00"));
+        assert_eq!(yesterday.worked, chrono::Duration::hours(6));
+        assert_eq!(yesterday.week_worked, chrono::Duration::hours(6));
+
+        let week = WeekReport::collect(&db, now.date_naive(), chrono::Duration::hours(40), now, session_start);
+        assert_eq!(week.worked, chrono::Duration::hours(7));
+    }
+
+    #[test]
+    fn test_week_report() {
+        let now = at("2023-05-06T12:00:00+02:00");
+        let report = WeekReport::collect(&db(), now.date_naive(), chrono::Duration::hours(40), now, None);
+        assert_eq!(report.first_day, "2023-05-01".parse::<chrono::NaiveDate>().unwrap());
+        assert_eq!(report.worked, chrono::Duration::hours(5));
+        assert_eq!(report.expected, chrono::Duration::hours(32));
+        let durations = DurationFormat::default();
+        assert_eq!(
+            report.days.iter().map(|d| d.reason.clone()).collect::<Vec<_>>()[3..],
+            [Some("Vacation".to_string()), None, Some("Weekend".to_string()), Some("Weekend".to_string())]
+        );
+        assert!(format_day_line(&report.days[1], &durations, Locale::En, true).starts_with("Tue 2023-05-02: 4h0m0s  deviation: \x1b[31m-4h0m0s"));
+        assert!(render_week(Format::Plain, &durations, Locale::En, &report).ends_with("Week: 5h0m0s of 32h0m0s, deviation: -27h0m0s\n"));
+        assert!(render_week(Format::Markdown, &durations, Locale::En, &report).contains("| Thu 2023-05-04 | 0h0m0s | 0h0m0s | 0h0m0s | Vacation |\n"));
+        let clock = DurationFormat {
+            style: crate::duration::DurationStyle::Clock,
+            rounding: crate::duration::Rounding::Down,
+        };
+        assert!(render_week(Format::Plain, &clock, Locale::En, &report).ends_with("Week: 5:00 of 32:00, deviation: -27:00\n"));
+
+        let mut billed = report;
+        billed.bill(&BillingRounding {
+            scope: crate::billing::BillingScope::Entry,
+            increment: chrono::Duration::minutes(45),
+            rounding: crate::duration::Rounding::Up,
+        });
+        assert_eq!(billed.billed, Some(chrono::Duration::hours(6)));
+        assert_eq!(billed.days[4].billed, Some(chrono::Duration::zero()));
+        assert!(format_day_line(&billed.days[1], &durations, Locale::En, false).starts_with("Tue 2023-05-02: 4h0m0s (billed 4h30m0s)  deviation: -4h0m0s"));
+        assert!(render_week(Format::Plain, &durations, Locale::En, &billed).ends_with("Week: 5h0m0s (billed 6h0m0s) of 32h0m0s, deviation: -27h0m0s\n"));
+        let json: serde_json::Value = serde_json::from_str(&render_week(Format::Json, &durations, Locale::En, &billed)).unwrap();
+        assert_eq!(json["billed_seconds"], 6 * 3600);
+        assert_eq!(json["days"][2]["billed_seconds"], 90 * 60);
+
+        let german = render_week(Format::Markdown, &durations, Locale::De, &billed);
+        assert!(german.starts_with("## Woche ab Mo 01.05.2023\n\n| Tag | Gearbeitet | Soll | Abweichung | Hinweis |\n"));
+        assert!(german.contains("| Do 04.05.2023 | 0h0m0s (abgerechnet 0h0m0s) | 0h0m0s | 0h0m0s | Urlaub |\n"));
+        assert!(german.ends_with("Woche: 5h0m0s (abgerechnet 6h0m0s) von 32h0m0s, Abweichung: -27h0m0s\n"));
+    }
+
+    #[test]
+    fn test_format_detection() {
+        assert_eq!(Format::text(true, None), Format::Color);
+        assert_eq!(Format::text(true, Some("".into())), Format::Color);
+        assert_eq!(Format::text(true, Some("1".into())), Format::Plain);
+        assert_eq!(Format::text(false, None), Format::Plain);
+    }
+}
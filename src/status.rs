@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use crate::database::Database;
+use crate::duration::DurationFormat;
+use crate::locale::Locale;
+
+/// Live state older than this is stale, the monitor which wrote it is probably not running anymore
+const MAX_AGE_SECONDS: i64 = 10;
+
+/// Idle time below which the user counts as active
+const ACTIVE_SECONDS: i64 = 60;
+
+/// State of the running monitor. It is written every few seconds, so other processes
+/// (e.g. `worktime status`) can show the session in progress. As it is removed when monitoring
+/// ends cleanly, it also serves as heartbeat to recover the session after a crash.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LiveState {
+    pub updated: chrono::DateTime<chrono::offset::Local>,
+    /// start of the part of the session which is still running, i.e. after clock jumps and pauses.
+    /// None without a session or while paused.
+    pub session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+    pub idle_seconds: Option<i64>,
+    #[serde(default)]
+    pub last_activity: Option<chrono::DateTime<chrono::offset::Local>>,
+    pub paused: bool,
+    /// worktime of today and of the current week, including the running session
+    pub day_seconds: i64,
+    pub week_seconds: i64,
+}
+
+impl LiveState {
+    pub fn collect(
+        db: &Database,
+        session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+        idle_duration: Option<chrono::Duration>,
+        now: chrono::DateTime<chrono::offset::Local>,
+    ) -> Self {
+        Self {
+            updated: now,
+            session_start: session_start.and_then(|start| db.effective_session_start(start, now)),
+            idle_seconds: idle_duration.map(|idle| idle.num_seconds()),
+            last_activity: idle_duration.map(|idle| now - idle),
+            paused: db.is_in_open_excluded_range(),
+            day_seconds: db
+                .get_sum_with_session(Database::get_day_bounds(now), session_start, now)
+                .num_seconds(),
+            week_seconds: db
+                .get_sum_with_session(db.get_week_bounds(now), session_start, now)
+                .num_seconds(),
+        }
+    }
+
+    /// Writes into a temporary file first, so readers never see a half written state
+    pub fn store(self: &Self, path: &std::path::Path) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, json).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp_path, path).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&json).map_err(|e| e.to_string())
+    }
+
+    pub fn is_fresh(self: &Self, now: chrono::DateTime<chrono::offset::Local>) -> bool {
+        (now - self.updated).num_seconds() <= MAX_AGE_SECONDS
+    }
+
+    /// The running part of the session until the last input, at most until the last update.
+    /// This is what is recovered when monitoring did not end cleanly. None while paused.
+    pub fn unfinished_session(
+        self: &Self,
+    ) -> Option<(chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>)> {
+        if self.paused {
+            return None;
+        }
+        let end = self
+            .last_activity
+            .map_or(self.updated, |last_activity| std::cmp::min(last_activity, self.updated));
+        self.session_start.map(|start| (start, end))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Activity {
+    Active,
+    Idle,
+    Paused,
+    /// no monitor is running
+    Stopped,
+}
+
+impl Activity {
+    /// Name as used for the waybar css class
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            Activity::Active => "active",
+            Activity::Idle => "idle",
+            Activity::Paused => "paused",
+            Activity::Stopped => "stopped",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Status {
+    pub day: chrono::Duration,
+    pub week: chrono::Duration,
+    pub overtime: chrono::Duration,
+    pub activity: Activity,
+}
+
+/// Uses the live state of the monitor if it is fresh, the data file otherwise
+pub fn collect_status(
+    db: &Database,
+    live_state: Option<LiveState>,
+    overtime: chrono::Duration,
+    now: chrono::DateTime<chrono::offset::Local>,
+) -> Status {
+    match live_state.filter(|state| state.is_fresh(now)) {
+        Some(state) => Status {
+            day: chrono::Duration::seconds(state.day_seconds),
+            week: chrono::Duration::seconds(state.week_seconds),
+            overtime,
+            activity: if state.paused {
+                Activity::Paused
+            } else if state.idle_seconds.is_some_and(|idle| idle < ACTIVE_SECONDS) {
+                Activity::Active
+            } else {
+                Activity::Idle
+            },
+        },
+        None => Status {
+            day: db.get_sum_with_session(Database::get_day_bounds(now), None, now),
+            week: db.get_sum_with_session(db.get_week_bounds(now), None, now),
+            overtime,
+            activity: Activity::Stopped,
+        },
+    }
+}
+
+fn short_text(status: &Status, durations: &DurationFormat, locale: Locale) -> String {
+    format!("{} {}", locale.tr(status.activity.name()), durations.format(&status.day))
+}
+
+/// One line, e.g. for polybar or a shell prompt
+pub fn format_plain(status: &Status, durations: &DurationFormat, locale: Locale) -> String {
+    locale.format(
+        "{} day {} week {} overtime {}",
+        &[
+            &locale.tr(status.activity.name()),
+            &durations.format(&status.day),
+            &durations.format(&status.week),
+            &durations.format(&status.overtime),
+        ],
+    )
+}
+
+/// i3blocks reads full text, short text and color from consecutive lines
+pub fn format_i3blocks(status: &Status, durations: &DurationFormat, locale: Locale) -> String {
+    let color = match status.activity {
+        Activity::Active => "#00ff00",
+        Activity::Idle => "#ffff00",
+        Activity::Paused => "#ff00ff",
+        Activity::Stopped => "#888888",
+    };
+    format!(
+        "{}\n{}\n{}",
+        format_plain(status, durations, locale),
+        short_text(status, durations, locale),
+        color
+    )
+}
+
+/// waybar custom module json with `text`, `tooltip` and `class`. The class is never translated.
+pub fn format_waybar(status: &Status, durations: &DurationFormat, locale: Locale) -> String {
+    serde_json::json!({
+        "text": short_text(status, durations, locale),
+        "tooltip": locale.format(
+            "Today: {}\nWeek: {}\nOvertime: {}",
+            &[
+                &durations.format(&status.day),
+                &durations.format(&status.week),
+                &durations.format(&status.overtime),
+            ],
+        ),
+        "class": status.activity.name(),
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WorktimeEntry;
+
+    #[test]
+    fn test_collect_status_uses_fresh_live_state() {
+        let now: chrono::DateTime<chrono::offset::Local> = "2023-05-03T12:00:00+02:00".parse().unwrap();
+        let db = Database::in_memory(
+            vec![WorktimeEntry {
+                start: "2023-05-03T08:00:00+02:00".parse().unwrap(),
+                end: "2023-05-03T10:00:00+02:00".parse().unwrap(),
+                comments: "".into(),
+            }],
+            vec![],
+        );
+        let session_start = Some("2023-05-03T11:00:00+02:00".parse().unwrap());
+        let live_state = LiveState::collect(&db, session_start, Some(chrono::Duration::seconds(5)), now);
+        assert_eq!(live_state.day_seconds, 3 * 3600);
+
+        let status = collect_status(&db, Some(live_state.clone()), chrono::Duration::zero(), now);
+        assert_eq!(status.day, chrono::Duration::hours(3));
+        assert_eq!(status.activity, Activity::Active);
+
+        // stale live state falls back to the data file
+        let later = now + chrono::Duration::minutes(5);
+        let status = collect_status(&db, Some(live_state), chrono::Duration::zero(), later);
+        assert_eq!(status.day, chrono::Duration::hours(2));
+        assert_eq!(status.activity, Activity::Stopped);
+    }
+
+    #[test]
+    fn test_unfinished_session() {
+        let at = |time: &str| -> chrono::DateTime<chrono::offset::Local> {
+            format!("2023-05-03T{}:00+02:00", time).parse().unwrap()
+        };
+        let mut db = Database::in_memory(vec![], vec![]);
+        db.split_session_at_clock_jump(at("08:00"), at("10:00"), at("11:00"));
+        let live_state = LiveState::collect(&db, Some(at("08:00")), Some(chrono::Duration::minutes(20)), at("12:00"));
+        assert_eq!(live_state.session_start, Some(at("11:00")));
+        assert_eq!(live_state.day_seconds, 3 * 3600);
+        // idle time at the end is not recovered, nor the time before the clock jump (it is stored already)
+        assert_eq!(live_state.unfinished_session(), Some((at("11:00"), at("11:40"))));
+
+        db.begin_excluded_range(at("12:00"));
+        let live_state = LiveState::collect(&db, Some(at("08:00")), Some(chrono::Duration::zero()), at("12:05"));
+        assert!(live_state.paused);
+        assert_eq!(live_state.unfinished_session(), None);
+    }
+
+    #[test]
+    fn test_format_waybar() {
+        let status = Status {
+            day: chrono::Duration::hours(2),
+            week: chrono::Duration::hours(10),
+            overtime: chrono::Duration::minutes(-30),
+            activity: Activity::Idle,
+        };
+        let waybar = |locale| format_waybar(&status, &DurationFormat::default(), locale);
+        let json: serde_json::Value = serde_json::from_str(&waybar(Locale::En)).unwrap();
+        assert_eq!(json["class"], "idle");
+        assert!(json["text"].as_str().unwrap().starts_with("idle "));
+        assert!(json["tooltip"].as_str().unwrap().contains("Week: "));
+        assert!(json["tooltip"].as_str().unwrap().ends_with("Overtime: -0h30m0s"));
+
+        let json: serde_json::Value = serde_json::from_str(&waybar(Locale::De)).unwrap();
+        assert_eq!(json["class"], "idle");
+        assert!(json["text"].as_str().unwrap().starts_with("inaktiv "));
+        assert!(json["tooltip"].as_str().unwrap().ends_with("Überstunden: -0h30m0s"));
+    }
+}
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use crate::database::Database;
+use crate::models::WorktimeEntry;
+
+/// Worktime which was merged from the data file of another host. Stored in a side table, so
+/// the data file keeps its format.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct HostEntry {
+    pub start: chrono::DateTime<chrono::offset::Local>,
+    pub end: chrono::DateTime<chrono::offset::Local>,
+    pub host: String,
+}
+
+/// Name of this computer
+pub fn local_host_name() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+/// Reads worktime entries from a data file (e.g. the `~/.worktime.csv` of another computer)
+pub fn read_data_file(path: &std::path::Path) -> Result<Vec<WorktimeEntry>, String> {
+    let mut rdr = csv::Reader::from_path(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    rdr.deserialize()
+        .map(|result| result.map_err(|e| format!("{}: {}", path.display(), e)))
+        .collect()
+}
+
+/// Writes into a temporary file first, so other hosts never read a half written file
+fn write_csv<T: Serialize>(path: &std::path::Path, rows: &[T]) -> Result<(), String> {
+    // the whole file name, as files differing only in their extension must not share a temporary file
+    let mut tmp_path = path.as_os_str().to_os_string();
+    tmp_path.push(".tmp");
+    let tmp_path = std::path::PathBuf::from(tmp_path);
+    let mut wtr = csv::Writer::from_path(&tmp_path).map_err(|e| format!("{}: {}", tmp_path.display(), e))?;
+    for row in rows {
+        wtr.serialize(row).map_err(|e| format!("{}: {}", tmp_path.display(), e))?;
+    }
+    wtr.flush().map_err(|e| format!("{}: {}", tmp_path.display(), e))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Reads the host side table. A missing file is an empty table.
+pub fn read_host_entries(path: &std::path::Path) -> Result<Vec<HostEntry>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut rdr = csv::Reader::from_path(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    rdr.deserialize()
+        .map(|result| result.map_err(|e| format!("{}: {}", path.display(), e)))
+        .collect()
+}
+
+pub fn write_host_entries(path: &std::path::Path, host_entries: &[HostEntry]) -> Result<(), String> {
+    write_csv(path, host_entries)
+}
+
+/// Cuts `ranges` out of `entry`
+fn subtract(
+    entry: &WorktimeEntry,
+    ranges: impl Iterator<Item = (chrono::DateTime<chrono::offset::Local>, chrono::DateTime<chrono::offset::Local>)>,
+) -> Vec<WorktimeEntry> {
+    let mut ranges: Vec<_> = ranges.collect();
+    ranges.sort();
+    let mut pieces = Vec::new();
+    let mut uncovered_from = entry.start;
+    for (start, end) in ranges {
+        if start > uncovered_from {
+            pieces.push(WorktimeEntry {
+                start: uncovered_from,
+                end: std::cmp::min(start, entry.end),
+                comments: entry.comments.clone(),
+            });
+        }
+        uncovered_from = std::cmp::max(uncovered_from, end);
+        if uncovered_from >= entry.end {
+            return pieces;
+        }
+    }
+    pieces.push(WorktimeEntry {
+        start: uncovered_from,
+        end: entry.end,
+        comments: entry.comments.clone(),
+    });
+    pieces
+}
+
+/// The parts of `entries` which are not covered by the rows of `db`, by `in_progress` or by each
+/// other, so time worked on two computers at once is only counted once.
+pub fn plan_merge(db: &Database, in_progress: &[WorktimeEntry], mut entries: Vec<WorktimeEntry>) -> Vec<WorktimeEntry> {
+    entries.sort();
+    let mut added: Vec<WorktimeEntry> = Vec::new();
+    for entry in entries.iter().filter(|entry| entry.start < entry.end) {
+        // added parts are sorted and never overlap, so only the last ones can cover the entry
+        let added_before = added.iter().rev().take_while(|part| part.end > entry.start);
+        let covered = db
+            .overlapping_rows((entry.start, entry.end))
+            .chain(in_progress.iter())
+            .chain(added_before)
+            .map(|row| (row.start, row.end));
+        let parts = subtract(entry, covered);
+        added.extend(parts);
+    }
+    added
+}
+
+/// Adds the uncovered parts of `entries` to the database and tags them with `host`.
+/// `in_progress` are the pieces of the running session, which is not committed yet.
+pub fn merge_host(
+    db: &mut Database,
+    host_entries: &mut Vec<HostEntry>,
+    host: &str,
+    entries: Vec<WorktimeEntry>,
+    in_progress: &[WorktimeEntry],
+) -> Vec<WorktimeEntry> {
+    let added = plan_merge(db, in_progress, entries);
+    for entry in added.iter() {
+        db.insert_worktime(entry.clone());
+        host_entries.push(HostEntry {
+            start: entry.start,
+            end: entry.end,
+            host: host.to_string(),
+        });
+    }
+    added
+}
+
+/// Entries recorded on this computer: the rows without the parts merged from other hosts
+pub fn own_entries(rows: &[WorktimeEntry], host_entries: &[HostEntry]) -> Vec<WorktimeEntry> {
+    let mut merged: Vec<_> = host_entries.iter().map(|h| (h.start, h.end)).collect();
+    merged.sort();
+    // merged parts never overlap each other, so they are ordered by their ends as well
+    rows.iter()
+        .flat_map(|row| {
+            let first = merged.partition_point(|(_, end)| *end <= row.start);
+            subtract(row, merged[first..].iter().take_while(|(start, _)| *start < row.end).copied())
+        })
+        .collect()
+}
+
+/// Outcome of a shared-folder sync
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncSummary {
+    /// added entries per host
+    pub added: Vec<(String, Vec<WorktimeEntry>)>,
+    /// files of other hosts which could not be read
+    pub failed: Vec<String>,
+}
+
+/// Shared-folder sync: publishes the entries of this computer as `<host>.csv` in `sync_dir`
+/// and merges the files of all other hosts found there. A file which cannot be read is skipped.
+pub fn sync_folder(
+    db: &mut Database,
+    sync_dir: &std::path::Path,
+    host_file: &std::path::Path,
+    host: &str,
+    in_progress: &[WorktimeEntry],
+) -> Result<SyncSummary, String> {
+    let mut host_entries = read_host_entries(host_file)?;
+    std::fs::create_dir_all(sync_dir).map_err(|e| format!("{}: {}", sync_dir.display(), e))?;
+    write_csv(&sync_dir.join(format!("{}.csv", host)), &own_entries(db.rows(), &host_entries))?;
+
+    let mut summary = SyncSummary::default();
+    let dir = std::fs::read_dir(sync_dir).map_err(|e| format!("{}: {}", sync_dir.display(), e))?;
+    let mut paths: Vec<std::path::PathBuf> = dir.filter_map(|entry| entry.ok().map(|e| e.path())).collect();
+    paths.sort();
+    for path in paths {
+        let other_host = match path.file_stem() {
+            Some(stem) if path.extension().is_some_and(|ext| ext == "csv") => stem.to_string_lossy().to_string(),
+            _ => continue,
+        };
+        if other_host == host {
+            continue;
+        }
+        let entries = match read_data_file(&path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                summary.failed.push(err);
+                continue;
+            }
+        };
+        let added = merge_host(db, &mut host_entries, &other_host, entries, in_progress);
+        if !added.is_empty() {
+            summary.added.push((other_host, added));
+        }
+    }
+    if !summary.added.is_empty() {
+        write_host_entries(host_file, &host_entries)?;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start: &str, end: &str) -> WorktimeEntry {
+        WorktimeEntry {
+            start: format!("2023-05-03T{}:00+02:00", start).parse().unwrap(),
+            end: format!("2023-05-03T{}:00+02:00", end).parse().unwrap(),
+            comments: "".into(),
+        }
+    }
+
+    #[test]
+    fn test_plan_merge_coalesces_overlaps() {
+        let db = Database::in_memory(vec![entry("09:00", "11:00"), entry("13:00", "14:00")], vec![]);
+        let other = vec![
+            entry("10:00", "12:00"),
+            // fully covered
+            entry("13:15", "13:45"),
+            // overlapping each other
+            entry("15:00", "16:00"),
+            entry("15:30", "16:30"),
+            // containing an existing entry
+            entry("12:30", "14:30"),
+        ];
+        assert_eq!(
+            plan_merge(&db, &[], other),
+            vec![
+                entry("11:00", "12:00"),
+                entry("12:30", "13:00"),
+                entry("14:00", "14:30"),
+                entry("15:00", "16:00"),
+                entry("16:00", "16:30"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_host_and_own_entries() {
+        let mut db = Database::in_memory(vec![entry("09:00", "11:00")], vec![]);
+        let mut host_entries = Vec::new();
+        let added = merge_host(
+            &mut db,
+            &mut host_entries,
+            "laptop",
+            vec![entry("10:00", "12:00"), entry("14:00", "15:00")],
+            &[entry("14:30", "15:30")],
+        );
+        assert_eq!(added, vec![entry("11:00", "12:00"), entry("14:00", "14:30")]);
+        assert_eq!(host_entries[0].host, "laptop");
+
+        // merging the same file again adds nothing
+        let added = merge_host(&mut db, &mut host_entries, "laptop", vec![entry("10:00", "12:00")], &[]);
+        assert_eq!(added, vec![]);
+
+        assert_eq!(own_entries(db.rows(), &host_entries), vec![entry("09:00", "11:00")]);
+    }
+
+    #[test]
+    fn test_sync_folder_skips_unreadable_files() {
+        let dir = std::env::temp_dir().join(format!("worktime-sync-{}", std::process::id()));
+        let sync_dir = dir.join("sync");
+        std::fs::create_dir_all(&sync_dir).unwrap();
+        write_csv(&sync_dir.join("desktop.csv"), &[entry("12:00", "13:00")]).unwrap();
+        std::fs::write(sync_dir.join("broken.csv"), "start,end,comments\nyesterday,today,\n").unwrap();
+
+        let mut db = Database::in_memory(vec![entry("09:00", "11:00")], vec![]);
+        let summary = sync_folder(&mut db, &sync_dir, &dir.join("hosts.csv"), "laptop", &[]).unwrap();
+        assert_eq!(summary.added, vec![("desktop".to_string(), vec![entry("12:00", "13:00")])]);
+        assert_eq!(summary.failed.len(), 1);
+        assert!(summary.failed[0].contains("broken.csv"));
+        assert_eq!(read_data_file(&sync_dir.join("laptop.csv")).unwrap(), vec![entry("09:00", "11:00")]);
+        // the temporary file was renamed
+        assert!(!sync_dir.join("laptop.tmp").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use std::io::Write;
+use chrono::Datelike;
+use crate::database::Database;
+use crate::models::SpecialDayType;
+use crate::duration::DurationFormat;
+use crate::locale::Locale;
+use crate::utils::{escape_html, start_of_day};
+
+/// One row of a monthly timesheet
+#[derive(Debug, PartialEq)]
+pub struct TimesheetDay {
+    pub day: chrono::NaiveDate,
+    /// start of the first and end of the last worktime entry of the day
+    pub start: Option<chrono::DateTime<chrono::offset::Local>>,
+    pub end: Option<chrono::DateTime<chrono::offset::Local>>,
+    pub breaks: chrono::Duration,
+    pub worked: chrono::Duration,
+    pub expected: chrono::Duration,
+    pub special_day: Option<SpecialDayType>,
+}
+
+/// Parses a month given as `YYYY-MM` into its first day
+pub fn parse_month(text: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(&format!("{}-01", text), "%Y-%m-%d")
+        .map_err(|_| format!("invalid month '{}', expected YYYY-MM", text))
+}
+
+pub fn collect_month(
+    db: &Database,
+    first_day: chrono::NaiveDate,
+    weekly_worktime: chrono::Duration,
+) -> Vec<TimesheetDay> {
+    let mut result = Vec::new();
+    let mut day = first_day;
+    while day.month() == first_day.month() {
+        let next_day = day.succ_opt().unwrap();
+        let mut start = None;
+        let mut end = None;
+        let mut worked = chrono::Duration::zero();
+        for entry in db.query((start_of_day(day), start_of_day(next_day))) {
+            start = start.or(Some(entry.start));
+            end = Some(entry.end);
+            worked = worked + entry.duration();
+        }
+        let breaks = match (start, end) {
+            (Some(start), Some(end)) => (end - start) - worked,
+            _ => chrono::Duration::zero(),
+        };
+        result.push(TimesheetDay {
+            day,
+            start,
+            end,
+            breaks,
+            worked,
+            expected: db.get_expected_day_worktime(day, weekly_worktime),
+            special_day: db.get_special_day(day).map(|sd| sd.day_type.clone()),
+        });
+        day = next_day;
+    }
+    result
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; font-size: 10pt; margin: 2em; }
+h1 { font-size: 16pt; margin-bottom: 0.2em; }
+table.head td { padding: 0.1em 1em 0.1em 0; }
+table.sheet { border-collapse: collapse; width: 100%; margin-top: 1em; }
+table.sheet th, table.sheet td { border: 1px solid #888; padding: 0.2em 0.5em; text-align: right; }
+table.sheet th:first-child, table.sheet td:first-child, table.sheet td.type { text-align: left; }
+tr.weekend td, tr.special td { background: #eee; }
+tr.total td { font-weight: bold; border-top: 2px solid #000; }
+.signatures { display: flex; gap: 4em; margin-top: 4em; }
+.signature { flex: 1; border-top: 1px solid #000; padding-top: 0.3em; }
+@media print {
+  @page { size: A4 portrait; margin: 15mm; }
+  body { margin: 0; font-size: 9pt; }
+  tr { page-break-inside: avoid; }
+}
+";
+
+/// Renders a self-contained, printable HTML timesheet
+pub fn write_html<W: Write>(
+    out: &mut W,
+    employee_name: &str,
+    weekly_hours: i64,
+    days: &[TimesheetDay],
+    durations: &DurationFormat,
+    locale: Locale,
+) -> std::io::Result<()> {
+    let month = days.first().map(|d| locale.month(d.day)).unwrap_or_default();
+    let time_or_empty = |time: Option<chrono::DateTime<chrono::offset::Local>>| {
+        time.map(|t| t.format("%H:%M").to_string()).unwrap_or_default()
+    };
+    let duration_or_empty = |duration: chrono::Duration| {
+        if duration.is_zero() { String::new() } else { durations.format(&duration) }
+    };
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    let title = format!("{} {}", escape_html(employee_name), month);
+    writeln!(out, "<title>{}</title>", locale.format("Timesheet {}", &[&title]))?;
+    writeln!(out, "<style>{}</style>\n</head>\n<body>", STYLE)?;
+    writeln!(out, "<h1>{}</h1>", locale.format("Timesheet {}", &[&month]))?;
+    writeln!(out, "<table class=\"head\">")?;
+    writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", locale.tr("Employee:"), escape_html(employee_name))?;
+    writeln!(
+        out,
+        "<tr><td>{}</td><td>{}</td></tr>",
+        locale.tr("Contract hours:"),
+        locale.format("{}h per week", &[&weekly_hours])
+    )?;
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<table class=\"sheet\">")?;
+    let header: String = ["Day", "Start", "End", "Breaks", "Total", "Expected", "Type"]
+        .iter()
+        .map(|cell| format!("<th>{}</th>", locale.tr(cell)))
+        .collect();
+    writeln!(out, "<tr>{}</tr>", header)?;
+    let mut worked_sum = chrono::Duration::zero();
+    let mut expected_sum = chrono::Duration::zero();
+    for day in days {
+        let weekend = matches!(day.day.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun);
+        let (class, day_type) = match (&day.special_day, weekend) {
+            (Some(day_type), _) => ("special", locale.tr(&format!("{:?}", day_type)).to_string()),
+            (None, true) => ("weekend", locale.tr("Weekend").to_string()),
+            (None, false) => ("", String::new()),
+        };
+        writeln!(
+            out,
+            "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"type\">{}</td></tr>",
+            class,
+            locale.day(day.day),
+            time_or_empty(day.start),
+            time_or_empty(day.end),
+            duration_or_empty(day.breaks),
+            duration_or_empty(day.worked),
+            duration_or_empty(day.expected),
+            day_type
+        )?;
+        worked_sum = worked_sum + day.worked;
+        expected_sum = expected_sum + day.expected;
+    }
+    writeln!(
+        out,
+        "<tr class=\"total\"><td>{}</td><td></td><td></td><td></td><td>{}</td><td>{}</td><td class=\"type\">{}</td></tr>",
+        locale.tr("Total"),
+        durations.format(&worked_sum),
+        durations.format(&expected_sum),
+        locale.format("Balance: {}", &[&durations.format(&(worked_sum - expected_sum))])
+    )?;
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<div class=\"signatures\">")?;
+    writeln!(out, "<div class=\"signature\">{}</div>", locale.tr("Date, signature employee"))?;
+    writeln!(out, "<div class=\"signature\">{}</div>", locale.tr("Date, signature supervisor"))?;
+    writeln!(out, "</div>\n</body>\n</html>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{SpecialDayEntry, WorktimeEntry};
+
+    #[test]
+    fn test_collect_month() {
+        let db = Database::in_memory(
+            vec![
+                WorktimeEntry {
+                    start: "2023-05-02T08:00:00+02:00".parse().unwrap(),
+                    end: "2023-05-02T12:00:00+02:00".parse().unwrap(),
+                    comments: "".into(),
+                },
+                WorktimeEntry {
+                    start: "2023-05-02T12:30:00+02:00".parse().unwrap(),
+                    end: "2023-05-02T16:00:00+02:00".parse().unwrap(),
+                    comments: "".into(),
+                },
+            ],
+            vec![SpecialDayEntry {
+                day: "2023-05-01".parse().unwrap(),
+                day_type: SpecialDayType::Holiday,
+            }],
+        );
+        let days = collect_month(&db, parse_month("2023-05").unwrap(), chrono::Duration::hours(40));
+        assert_eq!(days.len(), 31);
+
+        assert_eq!(days[0].special_day, Some(SpecialDayType::Holiday));
+        assert_eq!(days[0].expected, chrono::Duration::zero());
+        assert_eq!(days[0].start, None);
+
+        assert_eq!(days[1].start, Some("2023-05-02T08:00:00+02:00".parse().unwrap()));
+        assert_eq!(days[1].end, Some("2023-05-02T16:00:00+02:00".parse().unwrap()));
+        assert_eq!(days[1].breaks, chrono::Duration::minutes(30));
+        assert_eq!(days[1].worked, chrono::Duration::minutes(7 * 60 + 30));
+        assert_eq!(days[1].expected, chrono::Duration::hours(8));
+
+        // saturday
+        assert_eq!(days[5].expected, chrono::Duration::zero());
+    }
+
+    #[test]
+    fn test_parse_month() {
+        assert_eq!(parse_month("2026-09"), Ok("2026-09-01".parse().unwrap()));
+        assert!(parse_month("2026-13").is_err());
+    }
+}
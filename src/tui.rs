@@ -0,0 +1,416 @@
+use std::io::Write;
+use std::sync::*;
+use chrono::Datelike;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use crate::billing::BillingRounding;
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::WorktimeEntry;
+use crate::report;
+use crate::duration::DurationFormat;
+use crate::locale::Locale;
+
+/// What the idle detector currently knows: start of the running session and time since last input
+pub type DetectorState = (
+    Option<chrono::DateTime<chrono::offset::Local>>,
+    Option<chrono::Duration>,
+);
+
+struct View {
+    /// 0 is today, -1 yesterday, ...
+    day_offset: i64,
+    /// text of the comment currently being entered
+    comment_input: Option<String>,
+    message: String,
+}
+
+type Line = (Color, String);
+
+/// What the overtime depends on: the day, the revision of the rows and the overtime configuration
+type OvertimeKey = (
+    chrono::NaiveDate,
+    u64,
+    i64,
+    chrono::DateTime<chrono::offset::Local>,
+    f64,
+    Option<BillingRounding>,
+);
+
+/// Overtime until the start of today. It goes through the whole history, so it is only calculated
+/// again when the stored rows, the day or the configuration changed.
+#[derive(Default)]
+struct OvertimeCache {
+    key: Option<OvertimeKey>,
+    until_today: chrono::Duration,
+}
+
+impl OvertimeCache {
+    /// Overtime including today's part so far. `today` are the worktime entries of today.
+    fn overtime(
+        self: &mut Self,
+        db: &Database,
+        cfg: &Config,
+        today: &[WorktimeEntry],
+        now: chrono::DateTime<chrono::offset::Local>,
+    ) -> chrono::Duration {
+        let billing = cfg.billing().filter(|_| cfg.billing_overtime);
+        let key = (
+            now.date_naive(),
+            db.revision(),
+            cfg.weekly_hours,
+            cfg.cutoff_datetime,
+            cfg.cutoff_day_overtime_hours,
+            billing,
+        );
+        if self.key != Some(key) {
+            self.until_today = report::overtime(db, cfg, now);
+            self.key = Some(key);
+        }
+        if now.date_naive() < cfg.cutoff_datetime.date_naive() {
+            return self.until_today;
+        }
+        let worked = match billing {
+            Some(billing) => billing.billed(today),
+            None => today.iter().fold(chrono::Duration::zero(), |sum, entry| sum + entry.duration()),
+        };
+        let expected = db.get_expected_day_worktime(now.date_naive(), chrono::Duration::hours(cfg.weekly_hours));
+        self.until_today + worked - expected
+    }
+}
+
+pub fn enter_terminal() -> std::io::Result<()> {
+    terminal::enable_raw_mode()?;
+    execute!(std::io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)
+}
+
+/// Restores the terminal. Harmless if the terminal UI was never entered.
+pub fn restore_terminal() {
+    let _ = execute!(std::io::stdout(), terminal::LeaveAlternateScreen, cursor::Show);
+    let _ = terminal::disable_raw_mode();
+}
+
+/// Worktime entries of `day`, including the pieces of the running session
+fn day_entries(
+    db: &Database,
+    day: chrono::NaiveDate,
+    session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+    now: chrono::DateTime<chrono::offset::Local>,
+) -> Vec<(WorktimeEntry, bool)> {
+    let (day_start, day_end) = Database::get_day_bounds(crate::utils::start_of_day(day));
+    let mut entries: Vec<(WorktimeEntry, bool)> =
+        db.query((day_start, day_end)).map(|entry| (entry, false)).collect();
+    if let Some(session_start) = session_start {
+        for piece in db.in_progress_pieces(session_start, now) {
+            if piece.end <= day_start || piece.start >= day_end {
+                continue;
+            }
+            let running = piece.end == now;
+            let piece = WorktimeEntry {
+                start: std::cmp::max(piece.start, day_start),
+                end: std::cmp::min(piece.end, day_end),
+                comments: piece.comments,
+            };
+            entries.push((piece, running));
+        }
+    }
+    entries
+}
+
+fn timeline_lines(entries: &[(WorktimeEntry, bool)], durations: &DurationFormat, locale: Locale) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut previous_end: Option<chrono::DateTime<chrono::offset::Local>> = None;
+    for (entry, running) in entries {
+        if let Some(previous_end) = previous_end {
+            lines.push((
+                Color::DarkGrey,
+                format!(
+                    "                     {}",
+                    locale.format("break {}", &[&durations.format(&(entry.start - previous_end))])
+                ),
+            ));
+        }
+        let end = if *running { format!("  {:<6}", locale.tr("now")) } else { entry.end.format("%T").to_string() };
+        let comment = if entry.comments.is_empty() { String::new() } else { format!(" \"{}\"", entry.comments) };
+        lines.push((
+            if *running { Color::Yellow } else { Color::Green },
+            format!("{} - {}  {}{}", entry.start.format("%T"), end, durations.format(&entry.duration()), comment),
+        ));
+        previous_end = Some(entry.end);
+    }
+    if lines.is_empty() {
+        lines.push((Color::DarkGrey, locale.tr("no worktime recorded").to_string()));
+    }
+    lines
+}
+
+fn week_lines(
+    db: &Database,
+    day: chrono::NaiveDate,
+    session_start: Option<chrono::DateTime<chrono::offset::Local>>,
+    now: chrono::DateTime<chrono::offset::Local>,
+    weekly_worktime: chrono::Duration,
+    durations: &DurationFormat,
+    locale: Locale,
+) -> Vec<Line> {
+    let first_day = db.get_week_start(day);
+    let sums: Vec<(chrono::NaiveDate, chrono::Duration)> = (0..7)
+        .map(|i| {
+            let day = first_day + chrono::Duration::days(i);
+            let sum = day_entries(db, day, session_start, now)
+                .iter()
+                .fold(chrono::Duration::zero(), |sum, (entry, _)| sum + entry.duration());
+            (day, sum)
+        })
+        .collect();
+    let scale = sums
+        .iter()
+        .map(|(_, sum)| *sum)
+        .max()
+        .unwrap_or_default()
+        .max(weekly_worktime / 5)
+        .max(chrono::Duration::hours(1));
+    const BAR_WIDTH: i64 = 20;
+
+    let mut lines = Vec::new();
+    let mut week_sum = chrono::Duration::zero();
+    for (bar_day, sum) in sums {
+        let filled = (sum.num_seconds() * BAR_WIDTH / scale.num_seconds()) as usize;
+        let color = if bar_day == day { Color::Yellow } else { Color::Green };
+        lines.push((
+            color,
+            format!(
+                "{} {}{} {}",
+                locale.weekday(bar_day.weekday()),
+                "█".repeat(filled),
+                "░".repeat(BAR_WIDTH as usize - filled),
+                durations.format(&sum)
+            ),
+        ));
+        week_sum = week_sum + sum;
+    }
+    lines.push((Color::Reset, locale.format("Week: {}", &[&durations.format(&week_sum)])));
+    lines
+}
+
+fn activity_line(paused: bool, state: &DetectorState, locale: Locale) -> Line {
+    if paused {
+        return (Color::Magenta, locale.tr("Paused").to_string());
+    }
+    match state.1 {
+        Some(idle) if idle.num_seconds() < 60 => {
+            (Color::Green, locale.format("Active ({}s since last input)", &[&idle.num_seconds()]))
+        }
+        Some(idle) => (
+            Color::DarkYellow,
+            locale.format("Idle for {}m {}s", &[&(idle.num_seconds() / 60), &(idle.num_seconds() % 60)]),
+        ),
+        None => (Color::DarkGrey, locale.tr("No activity detected yet").to_string()),
+    }
+}
+
+fn print_line<W: Write>(out: &mut W, column: u16, row: u16, width: u16, line: &Line) -> std::io::Result<()> {
+    let text: String = line.1.chars().take(width as usize).collect();
+    queue!(out, cursor::MoveTo(column, row), SetForegroundColor(line.0), Print(text), ResetColor)
+}
+
+fn draw(
+    db: &Database,
+    cfg: &Config,
+    view: &View,
+    state: &DetectorState,
+    overtime_cache: &mut OvertimeCache,
+) -> std::io::Result<()> {
+    let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+    let day = now.date_naive() + chrono::Duration::days(view.day_offset);
+    let weekly_worktime = chrono::Duration::hours(cfg.weekly_hours);
+    let durations = cfg.duration_format();
+    let (columns, rows) = terminal::size()?;
+    let left_width = columns * 3 / 5;
+
+    let entries = day_entries(db, day, state.0, now);
+    let day_sum = entries
+        .iter()
+        .fold(chrono::Duration::zero(), |sum, (entry, _)| sum + entry.duration());
+    let today: Vec<WorktimeEntry> = if view.day_offset == 0 {
+        entries.iter().map(|(entry, _)| entry.clone()).collect()
+    } else {
+        day_entries(db, now.date_naive(), state.0, now).into_iter().map(|(entry, _)| entry).collect()
+    };
+    let overtime = overtime_cache.overtime(db, cfg, &today, now);
+
+    let mut out = std::io::stdout();
+    queue!(out, terminal::Clear(terminal::ClearType::All))?;
+    let locale = cfg.locale;
+    let title = format!(
+        "worktime  {}{}",
+        locale.day(day),
+        if view.day_offset == 0 { format!(" {}", locale.tr("(today)")) } else { String::new() }
+    );
+    print_line(&mut out, 0, 0, left_width, &(Color::Reset, title))?;
+    let activity = activity_line(db.is_in_open_excluded_range(), state, locale);
+    print_line(&mut out, left_width, 0, columns - left_width, &activity)?;
+    print_line(
+        &mut out,
+        0,
+        1,
+        columns,
+        &(
+            Color::Reset,
+            locale.format(
+                "Day: {}  Expected: {}  Overtime: {}",
+                &[
+                    &durations.format(&day_sum),
+                    &durations.format(&db.get_expected_day_worktime(day, weekly_worktime)),
+                    &durations.format(&overtime),
+                ],
+            ),
+        ),
+    )?;
+
+    // keep the end of the timeline visible, it is the interesting part of today:
+    let pane_height = rows.saturating_sub(5) as usize;
+    let timeline = timeline_lines(&entries, &durations, locale);
+    let skip = timeline.len().saturating_sub(pane_height);
+    for (i, line) in timeline.iter().skip(skip).enumerate() {
+        print_line(&mut out, 0, 3 + i as u16, left_width.saturating_sub(1), line)?;
+    }
+    for (i, line) in week_lines(db, day, state.0, now, weekly_worktime, &durations, locale).iter().take(pane_height).enumerate() {
+        print_line(&mut out, left_width, 3 + i as u16, columns - left_width, line)?;
+    }
+
+    let footer = match &view.comment_input {
+        Some(text) => (Color::Reset, locale.format("Comment for current session: {}_", &[text])),
+        None if !view.message.is_empty() => (Color::Cyan, view.message.clone()),
+        None => (
+            Color::DarkGrey,
+            locale.tr("[c] comment  [p] pause/resume  [←/→] previous/next day  [t] today  [q] quit").to_string(),
+        ),
+    };
+    print_line(&mut out, 0, rows.saturating_sub(1), columns, &footer)?;
+    out.flush()
+}
+
+/// Runs the full-screen terminal UI until the user quits or `should_stop` returns true.
+///
+/// `poll_detector` returns the current idle detector state. Returns an error if the terminal
+/// could not be used.
+pub fn run(
+    database: Arc<Mutex<Database>>,
+    cfg: &RwLock<Config>,
+    poll_detector: impl Fn() -> DetectorState,
+    should_stop: impl Fn() -> bool,
+) -> std::io::Result<()> {
+    enter_terminal()?;
+    let mut view = View {
+        day_offset: 0,
+        comment_input: None,
+        message: String::new(),
+    };
+    let mut overtime_cache = OvertimeCache::default();
+    while !should_stop() {
+        let state = poll_detector();
+        if let Err(err) = draw(&database.lock().unwrap(), &cfg.read().unwrap(), &view, &state, &mut overtime_cache) {
+            restore_terminal();
+            return Err(err);
+        }
+
+        if !event::poll(std::time::Duration::from_secs(1)).unwrap_or(false) {
+            continue;
+        }
+        let Ok(Event::Key(key)) = event::read() else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        view.message.clear();
+        let now: chrono::DateTime<chrono::offset::Local> = std::time::SystemTime::now().into();
+        let locale = cfg.read().unwrap().locale;
+
+        if let Some(text) = view.comment_input.as_mut() {
+            match key.code {
+                KeyCode::Enter => {
+                    let text = view.comment_input.take().unwrap();
+                    match state.0 {
+                        Some(session_start) => {
+                            database.lock().unwrap().set_session_comment(session_start, text);
+                            view.message = locale.tr("Comment set").to_string();
+                        }
+                        None => view.message = locale.tr("No session running").to_string(),
+                    }
+                }
+                KeyCode::Esc => view.comment_input = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                _ => (),
+            }
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Char('c') => view.comment_input = Some(String::new()),
+            KeyCode::Char('p') => {
+                let mut db = database.lock().unwrap();
+                if db.is_in_open_excluded_range() {
+                    db.end_excluded_range(now);
+                    view.message = locale.tr("Resumed").to_string();
+                } else {
+                    db.begin_excluded_range(now);
+                    view.message = locale.tr("Paused, no worktime is recorded until resumed").to_string();
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') => view.day_offset -= 1,
+            KeyCode::Right | KeyCode::Char('l') => view.day_offset = std::cmp::min(view.day_offset + 1, 0),
+            KeyCode::Char('t') => view.day_offset = 0,
+            _ => (),
+        }
+    }
+    restore_terminal();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start: &str, end: &str) -> WorktimeEntry {
+        WorktimeEntry {
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+            comments: "".into(),
+        }
+    }
+
+    #[test]
+    fn test_overtime_cache() {
+        let cfg = Config {
+            weekly_hours: 40,
+            cutoff_datetime: "2023-05-01T00:00:00+02:00".parse().unwrap(),
+            ..Default::default()
+        };
+        // monday 9 hours, tuesday 8 hours
+        let mut db = Database::in_memory(
+            vec![
+                entry("2023-05-01T08:00:00+02:00", "2023-05-01T17:00:00+02:00"),
+                entry("2023-05-02T08:00:00+02:00", "2023-05-02T16:00:00+02:00"),
+            ],
+            vec![],
+        );
+        let now = "2023-05-03T12:00:00+02:00".parse().unwrap();
+        let today = vec![entry("2023-05-03T08:00:00+02:00", "2023-05-03T12:00:00+02:00")];
+        let mut cache = OvertimeCache::default();
+        // one hour before today, four hours short of today's eight
+        assert_eq!(cache.overtime(&db, &cfg, &today, now), chrono::Duration::hours(1 - 4));
+        assert_eq!(cache.overtime(&db, &cfg, &today, now), chrono::Duration::hours(-3));
+
+        // a change of the stored rows is picked up
+        db.insert_worktime(entry("2023-05-02T18:00:00+02:00", "2023-05-02T19:00:00+02:00"));
+        assert_eq!(cache.overtime(&db, &cfg, &today, now), chrono::Duration::hours(-2));
+    }
+}
@@ -1,7 +1,41 @@
+use chrono::{offset::TimeZone, Local};
+
+/// Duration in the default style, e.g. `7h45m0s`. See `DurationFormat` for other styles.
 pub fn format_chrono_duration(duration: &chrono::Duration) -> String {
-    let sec_total = duration.num_seconds();
-    let hours = sec_total / 60 / 60;
-    let mins = (sec_total - hours * 60 * 60) / 60;
-    let secs = sec_total - hours * 60 * 60 - mins * 60;
-    format!("{}h{}m{}s", hours, mins, secs)
+    crate::duration::DurationFormat::default().format(duration)
+}
+
+/// Local midnight at the beginning of `day`. Where a DST change skips midnight, the day starts when the clock jumps.
+pub fn start_of_day(day: chrono::NaiveDate) -> chrono::DateTime<chrono::offset::Local> {
+    local_time_on(day, chrono::NaiveTime::MIN)
+}
+
+/// The first instant showing `time` on `day`, or the first one after it, if a DST change skips `time`.
+pub fn local_time_on(day: chrono::NaiveDate, time: chrono::NaiveTime) -> chrono::DateTime<chrono::offset::Local> {
+    let naive = day.and_time(time);
+    (0..=24 * 60)
+        .find_map(|minutes| {
+            Local
+                .from_local_datetime(&(naive + chrono::Duration::minutes(minutes)))
+                .earliest()
+        })
+        .unwrap()
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Asks a question on stdin, returns true if the user answered with yes
+pub fn ask_yes_no(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    std::io::Write::flush(&mut std::io::stdout()).unwrap();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
 }